@@ -4,6 +4,8 @@ require 'yaml'
 ID_MAP = YAML.load(File.read "def.yaml")
 
 
+# 活用する語の品詞
+# dict-to-mozc(--format byhand)はdef.yamlの同じ名前の定義を用いるので、変更するときはdef.yamlも合わせて変更すること。
 BYHAND_CLSMAP = {
   "一段動詞える" => ->(wp) {
     wp.w "動詞,非自立,*,*,一段,仮定形,える", "る"
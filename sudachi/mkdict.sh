@@ -34,17 +34,20 @@ cargo build --release
 PROG=$(find target -name dict-to-mozc)
 echo "PROG=" $PROG
 
-cat csv/small_lex.csv csv/core_lex.csv csv/notcore_lex.csv > all.csv
+INPUTS="--input sudachi:csv/small_lex.csv --input sudachi:csv/core_lex.csv --input sudachi:csv/notcore_lex.csv"
 
 wget -nc https://github.com/google/mozc/raw/refs/heads/master/src/data/dictionary_oss/id.def
 
 # SudachiDict
-$PROG -i ./id.def -f ./all.csv -s > ./$SYSTEMDIC.txt
+$PROG -i ./id.def $INPUTS > ./$SYSTEMDIC.txt
 
 # userdic
-$PROG -i ./id.def -f all.csv -s -U > ./$USERDIC
-split --numeric-suffixes=1 -l 1000000 --additional-suffix=.txt $USERDIC $USERDIC-
-rm $USERDIC
+$PROG -i ./id.def $INPUTS -U --output-dir ./$USERDIC.d
+# 配布物のファイル名は、これまでどおり$USERDIC-NN.txtとする
+for f in ./$USERDIC.d/user_dic-*.txt; do
+  mv "$f" "./$USERDIC-${f##*/user_dic-}"
+done
+rmdir ./$USERDIC.d
 
 mkdir -p ../release
 [[ -e ../release/${USERDIC}.tar.xz ]] && rm ../release/${USERDIC}.tar.xz
@@ -0,0 +1,178 @@
+//! Mozcのシステム辞書(src/data/dictionary_oss/dictionary*.txt)
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// 既にMozcの辞書にあるとみなす条件
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BaselineMatch {
+    /// 読みと表記が同じ
+    #[default]
+    Surface,
+    /// 読み、表記、品詞IDが同じ
+    Id,
+}
+
+impl std::str::FromStr for BaselineMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "surface" => Ok(BaselineMatch::Surface),
+            "id" => Ok(BaselineMatch::Id),
+            _ => Err(format!("unknown baseline match: {} (surface, id)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for BaselineMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BaselineMatch::Surface => "surface",
+            BaselineMatch::Id => "id",
+        })
+    }
+}
+
+/// Mozcのシステム辞書のエントリー
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BaselineEntry {
+    /// 品詞ID(左文脈ID)
+    pub id: i32,
+    pub cost: i32,
+}
+
+/// Mozcのシステム辞書
+/// 読みと表記ごとに、品詞IDとコストを保持する。
+#[derive(Clone, Debug, Default)]
+pub struct Baseline {
+    entries: HashMap<(String, String), Vec<BaselineEntry>>,
+    files: Vec<PathBuf>,
+}
+
+impl Baseline {
+    /// ディレクトリのdictionary*.txtを、ファイル名の順に読み込む
+    /// 行は、システム辞書型式の出力と同じく、読み、左文脈ID、右文脈ID、コスト、表記のタブ区切り。
+    pub fn load(dir: &Path) -> Result<Self, Error> {
+        let pattern = dir.join("dictionary*.txt");
+        let mut files: Vec<PathBuf> = glob::glob(&pattern.to_string_lossy())
+            .map_err(|e| Error::Config { path: dir.to_path_buf(), message: e.to_string() })?
+            .filter_map(Result::ok)
+            .collect();
+        files.sort();
+        if files.is_empty() {
+            return Err(Error::Config { path: dir.to_path_buf(), message: "no dictionary*.txt found".to_string() });
+        }
+        let mut baseline = Self::default();
+        for path in &files {
+            baseline.read_file(path)?;
+        }
+        baseline.files = files;
+        Ok(baseline)
+    }
+
+    fn read_file(&mut self, path: &Path) -> Result<(), Error> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 5 {
+                return Err(Error::Columns { path: path.to_path_buf(), line: i as u64 + 1, expected: 5, found: fields.len() });
+            }
+            let number = |column: usize, name: &'static str| fields[column].parse::<i32>().map_err(|_| Error::Field {
+                path: path.to_path_buf(),
+                line: i as u64 + 1,
+                column,
+                name,
+                value: fields[column].to_string(),
+                reason: "not a number".to_string(),
+            });
+            let entry = BaselineEntry { id: number(1, "left_id")?, cost: number(3, "cost")? };
+            self.entries.entry((fields[0].to_string(), fields[4].to_string())).or_default().push(entry);
+        }
+        Ok(())
+    }
+
+    /// 読み込んだファイル
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// 読みと表記が同じエントリー
+    pub fn get(&self, yomi: &str, surface: &str) -> &[BaselineEntry] {
+        self.entries.get(&(yomi.to_string(), surface.to_string())).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// 既にMozcの辞書にあるか
+    pub fn contains(&self, yomi: &str, surface: &str, hinshi_id: i32, by: BaselineMatch) -> bool {
+        let found = self.get(yomi, surface);
+        match by {
+            BaselineMatch::Surface => !found.is_empty(),
+            BaselineMatch::Id => found.iter().any(|e| e.id == hinshi_id),
+        }
+    }
+
+    /// 読みごとの、最も小さいコスト
+    pub fn min_costs_by_yomi(&self) -> HashMap<&str, i32> {
+        let mut costs: HashMap<&str, i32> = HashMap::new();
+        for ((yomi, _), entries) in &self.entries {
+            for entry in entries {
+                costs.entry(yomi.as_str())
+                    .and_modify(|cost| *cost = (*cost).min(entry.cost))
+                    .or_insert(entry.cost);
+            }
+        }
+        costs
+    }
+
+    /// 全てのエントリー
+    pub fn iter(&self) -> impl Iterator<Item = (&(String, String), &Vec<BaselineEntry>)> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_file;
+
+    fn read(name: &str, text: &str) -> Result<Baseline, Error> {
+        let path = temp_file(name, text);
+        let mut baseline = Baseline::default();
+        let result = baseline.read_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        result.map(|_| baseline)
+    }
+
+    #[test]
+    fn reads_entries() {
+        let baseline = read("ok.txt", "あ\t1\t1\t5000\t亜\n\nあ\t2\t2\t4000\t亜\n").unwrap();
+        assert_eq!(baseline.get("あ", "亜").len(), 2);
+        assert_eq!(baseline.min_costs_by_yomi().get("あ"), Some(&4000));
+    }
+
+    #[test]
+    fn short_line_is_a_columns_error() {
+        match read("columns.txt", "あ\t1\t1\t5000\t亜\nい\t1\t1\n") {
+            Err(Error::Columns { line, expected, found, .. }) => assert_eq!((line, expected, found), (2, 5, 3)),
+            other => panic!("unexpected: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn non_number_is_a_field_error() {
+        match read("field.txt", "あ\t1\t1\tx\t亜\n") {
+            Err(err @ Error::Field { .. }) => {
+                assert_eq!(err.line(), Some(1));
+                let Error::Field { column, name, value, .. } = &err else { unreachable!() };
+                assert_eq!((*column, *name, value.as_str()), (3, "cost", "x"));
+                assert!(err.to_string().ends_with(":1: field cost (column 4): not a number: \"x\""), "{}", err);
+            },
+            other => panic!("unexpected: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! ByHand辞書(byhand/dict.csv)の品詞定義(byhand/def.yaml)
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::Error;
+
+// def.yamlの品詞の展開で、classを含められる深さ
+const MAX_DEPTH: usize = 8;
+
+/// 品詞定義の1行
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ByhandRule {
+    /// id.defの品詞と、読みと表記の末尾から取り除く文字列
+    Pos { pos: String, strip: String },
+    /// 別の品詞の展開をそのまま含める
+    Class(String),
+}
+
+/// 展開された品詞
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ByhandPos<'a> {
+    /// id.defの品詞
+    pub pos: &'a str,
+    /// 読みと表記の末尾から取り除く文字列
+    pub strip: &'a str,
+}
+
+/// def.yamlの内容
+/// dict.csvの品詞(名詞サ変など)をキーとし、id.defの品詞のリストを値とする。
+/// リストの要素は、id.defの品詞の文字列、`{ pos: ..., strip: ... }`、`{ class: ... }`のいずれか。
+#[derive(Clone, Debug, Default)]
+pub struct ByhandDef {
+    classes: HashMap<String, Vec<ByhandRule>>,
+}
+
+impl ByhandDef {
+    /// YAMLの品詞定義を読み込む
+    pub fn from_yaml(text: &str) -> Result<Self, String> {
+        let table: Option<serde_yaml::Mapping> = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+        let mut classes = HashMap::new();
+        for (class, rules) in table.iter().flatten() {
+            let class = class.as_str()
+                .ok_or_else(|| format!("POS name must be a string: {:?}", class))?;
+            let rules = match rules {
+                serde_yaml::Value::Sequence(list) => list.iter()
+                    .map(|rule| parse_rule(class, rule))
+                    .collect::<Result<Vec<_>, _>>()?,
+                rule => vec![parse_rule(class, rule)?],
+            };
+            classes.insert(class.to_string(), rules);
+        }
+        let def = Self { classes };
+        // 存在しない品詞や循環するclassは、読み込み時にエラーとする
+        let mut names: Vec<&String> = def.classes.keys().collect();
+        names.sort();
+        for class in names {
+            def.expand(class)?;
+        }
+        Ok(def)
+    }
+
+    /// 品詞定義のファイルを読み込む
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        ByhandDef::from_yaml(&text)
+            .map_err(|message| Error::Config { path: path.to_path_buf(), message })
+    }
+
+    /// dict.csvの品詞を、id.defの品詞に展開する
+    /// 品詞定義にない場合は、id.defの品詞が直接書かれたものとみなす。
+    /// `サ変一段動詞える/未然ウ接続,命令ｙｏ`のように`/`の後に活用形を並べると、その活用形を除く。
+    /// 活用形を除く指定は、品詞定義にある品詞にのみ書ける(品詞名の誤りで、指定が黙って無視されないようにする)。
+    pub fn expand_spec<'a>(&'a self, spec: &'a str) -> Result<Vec<ByhandPos<'a>>, String> {
+        let (class, excludes) = match spec.split_once('/') {
+            Some((class, excludes)) => (class, excludes.split(',').collect()),
+            None => (spec, Vec::new()),
+        };
+        if !self.classes.contains_key(class) {
+            if !excludes.is_empty() {
+                return Err(format!("unknown class: {} (excluded forms need a class defined in def.yaml)", class));
+            }
+            return Ok(vec![ByhandPos { pos: class, strip: "" }]);
+        }
+        let mut expanded = self.expand(class)?;
+        // 活用形はid.defの品詞の6番目の項目
+        expanded.retain(|p| !p.pos.split(',').nth(5).is_some_and(|form| excludes.contains(&form)));
+        Ok(expanded)
+    }
+
+    fn expand(&self, class: &str) -> Result<Vec<ByhandPos<'_>>, String> {
+        let mut expanded = Vec::new();
+        self.expand_into(class, 0, &mut expanded)?;
+        Ok(expanded)
+    }
+
+    fn expand_into<'a>(&'a self, class: &str, depth: usize, expanded: &mut Vec<ByhandPos<'a>>) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("{}: class nesting is too deep", class));
+        }
+        let rules = self.classes.get(class)
+            .ok_or_else(|| format!("unknown class: {}", class))?;
+        for rule in rules {
+            match rule {
+                ByhandRule::Pos { pos, strip } => expanded.push(ByhandPos { pos, strip }),
+                ByhandRule::Class(c) => self.expand_into(c, depth + 1, expanded)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// 品詞定義の1行を読み込む
+fn parse_rule(class: &str, rule: &serde_yaml::Value) -> Result<ByhandRule, String> {
+    let field = |name: &str| rule.get(name).map(|v| {
+        v.as_str().map(|s| s.to_string()).ok_or_else(|| format!("{}: {} must be a string", class, name))
+    }).transpose();
+    match rule {
+        serde_yaml::Value::String(pos) => Ok(ByhandRule::Pos { pos: pos.clone(), strip: String::new() }),
+        serde_yaml::Value::Mapping(_) => match (field("pos")?, field("class")?) {
+            (Some(pos), None) => Ok(ByhandRule::Pos { pos, strip: field("strip")?.unwrap_or_default() }),
+            (None, Some(c)) => Ok(ByhandRule::Class(c)),
+            _ => Err(format!("{}: expected either pos or class", class)),
+        },
+        _ => Err(format!("{}: expected an id.def POS, {{ pos: ..., strip: ... }} or {{ class: ... }}", class)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEF: &str = r#"
+名詞: "名詞,一般,*,*,*,*,*"
+一段動詞:
+  - { pos: "動詞,自立,*,*,一段,基本形,*" }
+  - { pos: "動詞,自立,*,*,一段,未然形,*", strip: "る" }
+  - { pos: "動詞,自立,*,*,一段,命令ｙｏ,*", strip: "る" }
+サ変一段動詞:
+  - { pos: "名詞,サ変接続,*,*,*,*,*", strip: "る" }
+  - { class: 一段動詞 }
+"#;
+
+    fn pos<'a>(pos: &'a str, strip: &'a str) -> ByhandPos<'a> {
+        ByhandPos { pos, strip }
+    }
+
+    #[test]
+    fn expands_pos_and_strip() {
+        let def = ByhandDef::from_yaml(DEF).unwrap();
+        assert_eq!(def.expand_spec("名詞").unwrap(), [pos("名詞,一般,*,*,*,*,*", "")]);
+        assert_eq!(def.expand_spec("一段動詞").unwrap(), [
+            pos("動詞,自立,*,*,一段,基本形,*", ""),
+            pos("動詞,自立,*,*,一段,未然形,*", "る"),
+            pos("動詞,自立,*,*,一段,命令ｙｏ,*", "る"),
+        ]);
+    }
+
+    #[test]
+    fn expands_nested_class() {
+        let def = ByhandDef::from_yaml(DEF).unwrap();
+        let expanded = def.expand_spec("サ変一段動詞").unwrap();
+        assert_eq!(expanded.len(), 4);
+        assert_eq!(expanded[0], pos("名詞,サ変接続,*,*,*,*,*", "る"));
+        assert_eq!(expanded[1..], def.expand_spec("一段動詞").unwrap()[..]);
+    }
+
+    #[test]
+    fn slash_excludes_conjugation_forms() {
+        let def = ByhandDef::from_yaml(DEF).unwrap();
+        assert_eq!(def.expand_spec("サ変一段動詞/未然形,命令ｙｏ").unwrap(), [
+            pos("名詞,サ変接続,*,*,*,*,*", "る"),
+            pos("動詞,自立,*,*,一段,基本形,*", ""),
+        ]);
+    }
+
+    #[test]
+    fn unknown_spec_is_an_id_def_pos() {
+        let def = ByhandDef::from_yaml(DEF).unwrap();
+        assert_eq!(def.expand_spec("副詞,一般,*,*,*,*,*").unwrap(), [pos("副詞,一般,*,*,*,*,*", "")]);
+    }
+
+    #[test]
+    fn excludes_on_unknown_class_are_rejected() {
+        let def = ByhandDef::from_yaml(DEF).unwrap();
+        assert_eq!(def.expand_spec("サ変一段動詞エル/命令ｙｏ"),
+            Err("unknown class: サ変一段動詞エル (excluded forms need a class defined in def.yaml)".to_string()));
+    }
+
+    #[test]
+    fn bad_definitions_are_rejected() {
+        assert!(ByhandDef::from_yaml("a: { class: b }").is_err());
+        assert!(ByhandDef::from_yaml("a: { class: b }\nb: { class: a }").is_err());
+        assert!(ByhandDef::from_yaml("a: { pos: x, class: b }").is_err());
+    }
+
+    #[test]
+    fn shipped_definition_loads() {
+        let def = ByhandDef::from_yaml(include_str!("../../byhand/def.yaml")).unwrap();
+        let expanded = def.expand_spec("サ変一段動詞える/未然ウ接続,命令ｙｏ").unwrap();
+        assert!(expanded.iter().all(|p| !p.pos.contains("未然ウ接続") && !p.pos.contains("命令ｙｏ")));
+        assert_eq!(expanded[0], pos("名詞,サ変接続,*,*,*,*,*", "る"));
+    }
+}
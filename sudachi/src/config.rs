@@ -0,0 +1,309 @@
+//! dict-to-mozcの設定
+//!
+//! 次の順に読み込み、後のものほど優先する。
+//! 1. 組み込みの既定値
+//! 2. `$XDG_CONFIG_HOME/mozcdict-ext/config.toml`
+//! 3. カレントディレクトリの`mozcdict-ext.toml`(または`--config`で指定したファイル)
+//! 4. 環境変数(`MOZC_ID_FILE`、`MOZCDICT_EXT_SORT`など)
+//! 5. コマンドラインの引数
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use dict_to_mozc::utils::config_dir;
+use dict_to_mozc::{DEFAULT_COST_FLOOR_MARGIN, BaselineMatch, CollisionPolicy, Compression, CostProfileSpec, DictFormat, Error, MAX_USER_DICT_ENTRIES, InputEncoding, InputSpec, PriorityOrder, SortOrder};
+
+/// プロジェクトの設定ファイル
+pub const PROJECT_CONFIG: &str = "mozcdict-ext.toml";
+
+/// 環境変数の接頭辞
+/// id.defのみ、Rubyのスクリプトと同じMOZC_ID_FILEも用いる。
+pub const ENV_PREFIX: &str = "MOZCDICT_EXT_";
+
+/// 設定の各項目
+/// 設定ファイルのキーは、コマンドラインのオプション名と同じ(csv-file、id-defなど)。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub csv_file: Option<PathBuf>,
+    pub id_def: Option<PathBuf>,
+    pub user_dict: Option<bool>,
+    pub sudachi: Option<bool>,
+    pub neologd: Option<bool>,
+    pub utdict: Option<bool>,
+    #[serde(default, with = "parsed")]
+    pub format: Option<DictFormat>,
+    #[serde(default, with = "parsed")]
+    pub encoding: Option<InputEncoding>,
+    pub places: Option<bool>,
+    pub symbols: Option<bool>,
+    pub english: Option<bool>,
+    pub english_proper: Option<bool>,
+    pub fullwidth_english: Option<bool>,
+    pub fullwidth_english_proper: Option<bool>,
+    pub no_proper: Option<bool>,
+    #[serde(default, with = "parsed_list")]
+    pub input: Option<Vec<InputSpec>>,
+    #[serde(default, with = "parsed")]
+    pub priority: Option<PriorityOrder>,
+    pub strict: Option<bool>,
+    pub rejects: Option<PathBuf>,
+    pub exclude: Option<PathBuf>,
+    pub pos_mapping: Option<PathBuf>,
+    pub pos_map: Option<PathBuf>,
+    pub explain_pos: Option<PathBuf>,
+    #[serde(default, with = "parsed_list")]
+    pub cost_profile: Option<Vec<CostProfileSpec>>,
+    pub baseline: Option<PathBuf>,
+    #[serde(default, with = "parsed")]
+    pub baseline_match: Option<BaselineMatch>,
+    pub cost_reference: Option<PathBuf>,
+    pub cost_floor: Option<PathBuf>,
+    pub cost_floor_margin: Option<i32>,
+    #[serde(default, with = "parsed")]
+    pub collision: Option<CollisionPolicy>,
+    #[serde(default, with = "parsed")]
+    pub sort: Option<SortOrder>,
+    pub output: Option<PathBuf>,
+    #[serde(default, with = "parsed")]
+    pub compress: Option<Compression>,
+    pub user_db: Option<PathBuf>,
+    pub install: Option<bool>,
+    pub output_dir: Option<PathBuf>,
+    pub max_entries: Option<usize>,
+}
+
+// 設定の全ての項目に対して、同じ処理を行う
+macro_rules! for_each_field {
+    ($m:ident) => {
+        $m!(csv_file, id_def, user_dict, sudachi, neologd, utdict, format, encoding, places, symbols,
+            english, english_proper, fullwidth_english, fullwidth_english_proper, no_proper,
+            input, priority, strict, rejects, exclude, pos_mapping, pos_map, explain_pos, cost_profile, baseline, baseline_match, cost_reference, cost_floor, cost_floor_margin, collision, sort,
+            output, compress, user_db, install, output_dir, max_entries)
+    };
+}
+
+impl Config {
+    /// 組み込みの既定値
+    pub fn builtin() -> Self {
+        Self {
+            csv_file: Some(PathBuf::from("all.csv")),
+            id_def: Some(PathBuf::from("id.def")),
+            user_dict: Some(false),
+            sudachi: Some(false),
+            neologd: Some(false),
+            utdict: Some(false),
+            places: Some(false),
+            symbols: Some(false),
+            english: Some(false),
+            english_proper: Some(true),
+            fullwidth_english: Some(true),
+            fullwidth_english_proper: Some(true),
+            no_proper: Some(false),
+            input: Some(Vec::new()),
+            cost_profile: Some(Vec::new()),
+            priority: Some(PriorityOrder::default()),
+            strict: Some(false),
+            collision: Some(CollisionPolicy::default()),
+            sort: Some(SortOrder::default()),
+            install: Some(false),
+            max_entries: Some(MAX_USER_DICT_ENTRIES),
+            cost_floor_margin: Some(DEFAULT_COST_FLOOR_MARGIN),
+            ..Self::default()
+        }
+    }
+
+    /// 利用者の設定ファイル(`$XDG_CONFIG_HOME/mozcdict-ext/config.toml`)
+    pub fn user_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("config.toml"))
+    }
+
+    /// 設定ファイルを読み込む
+    /// 相対パスは、設定ファイルのあるディレクトリからのパスとみなす。
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let mut config: Config = toml::from_str(&text)
+            .map_err(|e| Error::Config { path: path.to_path_buf(), message: e.to_string().trim_end().to_string() })?;
+        if let Some(base) = path.parent() {
+            config.resolve_paths(base);
+        }
+        Ok(config)
+    }
+
+    /// ファイルがあれば読み込み、なければ空の設定とする
+    pub fn load_if_exists(path: &Path) -> Result<Self, Error> {
+        if path.exists() {
+            Config::load(path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+
+    /// 環境変数から読み込む
+    /// 変数名は、MOZCDICT_EXT_にオプション名を大文字にして続けたもの(MOZCDICT_EXT_USER_DICTなど)。
+    /// --inputはカンマ区切り、--cost-profileはセミコロン区切りで複数指定する。スイッチは1、true、yesで有効、0、false、noで無効になる。
+    pub fn from_env() -> Result<Self, Error> {
+        let env_error = |message: String| Error::Config { path: PathBuf::from("(environment)"), message };
+        let mut config = Config::default();
+        macro_rules! read_env {
+            ($($field:ident),*) => {
+                $(
+                    let name = format!("{}{}", ENV_PREFIX, stringify!($field).to_ascii_uppercase());
+                    if let Some(value) = std::env::var_os(&name) {
+                        let value = value.to_str().ok_or_else(|| env_error(format!("{}: not valid UTF-8", name)))?;
+                        config.$field = Some(FromEnv::from_env(value).map_err(|e| env_error(format!("{}: {}", name, e)))?);
+                    }
+                )*
+            };
+        }
+        for_each_field!(read_env);
+        if config.id_def.is_none() {
+            config.id_def = std::env::var_os("MOZC_ID_FILE").map(PathBuf::from);
+        }
+        Ok(config)
+    }
+
+    /// otherで指定された項目を上書きする
+    pub fn merge(&mut self, other: Config) {
+        macro_rules! merge_fields {
+            ($($field:ident),*) => {
+                $(
+                    if other.$field.is_some() {
+                        self.$field = other.$field;
+                    }
+                )*
+            };
+        }
+        for_each_field!(merge_fields);
+    }
+
+    // 相対パスをbaseからのパスにする
+    fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |path: &mut PathBuf| {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        };
+        for path in [&mut self.csv_file, &mut self.id_def, &mut self.rejects, &mut self.exclude,
+                     &mut self.pos_mapping, &mut self.pos_map, &mut self.explain_pos, &mut self.baseline, &mut self.cost_reference, &mut self.cost_floor,
+                     &mut self.output, &mut self.user_db, &mut self.output_dir].into_iter().flatten() {
+            resolve(path);
+        }
+        for input in self.input.iter_mut().flatten() {
+            resolve(&mut input.path);
+        }
+    }
+
+    /// 有効な設定をTOMLとして出力する
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string(self)
+            .map_err(|e| Error::Config { path: PathBuf::from("(effective config)"), message: e.to_string() })
+    }
+}
+
+// 環境変数の値を、設定の項目の型に変換する
+trait FromEnv: Sized {
+    fn from_env(value: &str) -> Result<Self, String>;
+}
+
+impl FromEnv for bool {
+    fn from_env(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" => Ok(true),
+            "0" | "false" | "no" | "" => Ok(false),
+            _ => Err(format!("expected true or false: {}", value)),
+        }
+    }
+}
+
+impl FromEnv for PathBuf {
+    fn from_env(value: &str) -> Result<Self, String> {
+        Ok(PathBuf::from(value))
+    }
+}
+
+impl FromEnv for Vec<InputSpec> {
+    fn from_env(value: &str) -> Result<Self, String> {
+        value.split(',').filter(|s| !s.is_empty()).map(|s| s.parse()).collect()
+    }
+}
+
+// 設定にカンマを含むので、セミコロンで区切る
+impl FromEnv for Vec<CostProfileSpec> {
+    fn from_env(value: &str) -> Result<Self, String> {
+        value.split(';').filter(|s| !s.is_empty()).map(|s| s.parse()).collect()
+    }
+}
+
+macro_rules! from_env_by_parse {
+    ($($t:ty),*) => {
+        $(
+            impl FromEnv for $t {
+                fn from_env(value: &str) -> Result<Self, String> {
+                    value.parse().map_err(|e| format!("{}", e))
+                }
+            }
+        )*
+    };
+}
+from_env_by_parse!(BaselineMatch, CollisionPolicy, DictFormat, InputEncoding, PriorityOrder, SortOrder, Compression, usize, i32);
+
+// FromStr、Displayを実装した型を、文字列として読み書きする
+mod parsed {
+    use super::*;
+
+    pub fn serialize<S: Serializer, T: Display>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(v) => serializer.serialize_str(&v.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: FromStr<Err = String>>(deserializer: D) -> Result<Option<T>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| s.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+// FromStr、Displayを実装した型のリストを、文字列の配列として読み書きする
+mod parsed_list {
+    use super::*;
+
+    pub fn serialize<S: Serializer, T: Display>(value: &Option<Vec<T>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(list) => serializer.collect_seq(list.iter().map(|v| v.to_string())),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: FromStr<Err = String>>(deserializer: D) -> Result<Option<Vec<T>>, D::Error> {
+        Option::<Vec<String>>::deserialize(deserializer)?
+            .map(|list| list.iter().map(|s| s.parse().map_err(serde::de::Error::custom)).collect())
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_round_trips_through_toml() {
+        let text = Config::builtin().to_toml().unwrap();
+        let parsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.to_toml().unwrap(), text);
+    }
+
+    #[test]
+    fn later_layer_turns_switch_off() {
+        let mut config = Config::builtin();
+        config.merge(toml::from_str("symbols = true\nenglish = true").unwrap());
+        config.merge(Config { symbols: Some(false), ..Config::default() });
+        assert_eq!(config.symbols, Some(false));
+        assert_eq!(config.english, Some(true));
+    }
+}
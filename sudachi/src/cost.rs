@@ -0,0 +1,328 @@
+//! 辞書データのコストから、Mozcのコストへの変換
+
+use std::collections::HashMap;
+
+use crate::baseline::Baseline;
+use crate::reader::DictFormat;
+use crate::utils::MOZC_USER_COST;
+
+/// --cost-floorで、システム辞書の最良の候補のコストに加える値の既定値
+pub const DEFAULT_COST_FLOOR_MARGIN: i32 = 100;
+
+/// curve=quantileで、minとmaxの指定がない場合の範囲
+/// 最も多く使われる単語でも、Mozcのユーザー辞書の単語(MOZC_USER_COST)から大きく外れないようにする。
+pub const DEFAULT_QUANTILE_BAND: (i32, i32) = (MOZC_USER_COST - 1500, MOZC_USER_COST + 1500);
+
+/// --explain-costで表示するコストの例
+pub const SAMPLE_COSTS: [i32; 10] = [-32768, -1, 0, 100, 1000, 3000, 5000, 10000, 20000, 32767];
+
+/// コストの曲線
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CostCurve {
+    /// base + コスト * scale
+    #[default]
+    Linear,
+    /// base + ln(1 + コスト) * scale
+    Log,
+    /// 入力元全体の、品詞の分類ごとのコストの分布から、百分位をminからmaxの範囲に割り当てる
+    /// minとmaxの指定がなければ、範囲はDEFAULT_QUANTILE_BANDとなる。
+    /// 読み込み中はLinearと同じく変換し、入力元を読み終えてから置き換える。
+    Quantile,
+}
+
+impl std::str::FromStr for CostCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(CostCurve::Linear),
+            "log" => Ok(CostCurve::Log),
+            "quantile" => Ok(CostCurve::Quantile),
+            _ => Err(format!("unknown cost curve: {} (linear, log, quantile)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for CostCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CostCurve::Linear => "linear",
+            CostCurve::Log => "log",
+            CostCurve::Quantile => "quantile",
+        })
+    }
+}
+
+/// 入力元ごとのコストの変換方法
+/// 既定値では、0から10000のコストを6000 + コスト / 10とし、負のコストは8000、10000を超えるコストは10000とする。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CostProfile {
+    /// 変換後のコストの基準
+    pub base: i32,
+    /// 元のコストに掛ける値
+    pub scale: f64,
+    pub curve: CostCurve,
+    /// 元のコストが負の場合のコスト
+    pub negative: i32,
+    /// 元のコストがこれを超える場合は、maxとする
+    pub raw_max: i32,
+    /// 変換後のコストの下限
+    pub min: i32,
+    /// 変換後のコストの上限
+    pub max: i32,
+}
+
+impl Default for CostProfile {
+    fn default() -> Self {
+        Self {
+            base: 6000,
+            scale: 0.1,
+            curve: CostCurve::Linear,
+            negative: 8000,
+            raw_max: 10000,
+            min: 0,
+            max: 10000,
+        }
+    }
+}
+
+impl CostProfile {
+    /// 元のコストを変換する
+    pub fn apply(&self, cost: i32) -> i32 {
+        let adjusted = if cost < 0 {
+            self.negative
+        } else if cost > self.raw_max {
+            self.max
+        } else {
+            let x = match self.curve {
+                CostCurve::Linear | CostCurve::Quantile => cost as f64,
+                CostCurve::Log => (1.0 + cost as f64).ln(),
+            };
+            // 変換後のコストは整数なので、小数点以下を切り捨てる
+            (self.base as f64 + (x * self.scale).trunc()) as i32
+        };
+        adjusted.clamp(self.min, self.max.max(self.min))
+    }
+
+    /// 百分位(0.0から1.0)を、minからmaxの範囲のコストにする
+    pub fn quantile(&self, percentile: f64) -> i32 {
+        let max = self.max.max(self.min);
+        self.min + ((max - self.min) as f64 * percentile.clamp(0.0, 1.0)).round() as i32
+    }
+
+    /// `base=5000,scale=0.05`のような指定で、項目を書き換える
+    /// `curve=quantile`を指定し、minとmaxがどちらも既定値のままの場合は、範囲をDEFAULT_QUANTILE_BANDとする。
+    pub fn set(&mut self, settings: &str) -> Result<(), String> {
+        let mut band_given = false;
+        for setting in settings.split(',').filter(|s| !s.is_empty()) {
+            let (name, value) = setting.split_once('=')
+                .ok_or_else(|| format!("expected name=value: {}", setting))?;
+            let int = || value.parse::<i32>().map_err(|_| format!("{}: not an integer: {}", name, value));
+            match name {
+                "base" => self.base = int()?,
+                "scale" => self.scale = value.parse().map_err(|_| format!("{}: not a number: {}", name, value))?,
+                "curve" => self.curve = value.parse()?,
+                "negative" => self.negative = int()?,
+                "raw-max" => self.raw_max = int()?,
+                "min" => { self.min = int()?; band_given = true; },
+                "max" => { self.max = int()?; band_given = true; },
+                _ => return Err(format!("unknown cost setting: {} (base, scale, curve, negative, raw-max, min, max)", name)),
+            }
+        }
+        if self.curve == CostCurve::Quantile && !band_given && (self.min, self.max) == (Self::default().min, Self::default().max) {
+            (self.min, self.max) = DEFAULT_QUANTILE_BAND;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for CostProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "base={},scale={},curve={},negative={},raw-max={},min={},max={}",
+            self.base, self.scale, self.curve, self.negative, self.raw_max, self.min, self.max)
+    }
+}
+
+/// 元のコストの一覧から、それぞれの百分位(0.0から1.0)を求める
+/// 元のコストが小さいほど百分位も小さくなる。同じコストには同じ百分位(順位の中央)を割り当て、
+/// ひとつしかない場合は0.5とする。
+pub fn percentiles(costs: &[i32]) -> Vec<f64> {
+    let mut sorted = costs.to_vec();
+    sorted.sort_unstable();
+    costs.iter().map(|&cost| percentile(&sorted, cost)).collect()
+}
+
+/// 並べ替えた元のコストの一覧の中での、costの百分位(0.0から1.0)
+/// 一覧にないコストは、その前後の間の順位とする。
+pub fn percentile(sorted: &[i32], cost: i32) -> f64 {
+    let last = sorted.len().saturating_sub(1);
+    if last == 0 {
+        return 0.5;
+    }
+    let first = sorted.partition_point(|&c| c < cost);
+    let end = sorted.partition_point(|&c| c <= cost);
+    // 同じコストの順位の中央。一覧にない場合は、前後の順位の中間になる
+    let rank = (first + end) as f64 / 2.0 - 0.5;
+    (rank / last as f64).clamp(0.0, 1.0)
+}
+
+/// --cost-profileの指定
+/// `sudachi:base=5000,scale=0.05,curve=log`のように、型式と設定をコロンで区切る。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CostProfileSpec {
+    pub format: DictFormat,
+    pub settings: String,
+}
+
+impl std::str::FromStr for CostProfileSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, settings) = s.split_once(':')
+            .ok_or_else(|| format!("expected FORMAT:SETTINGS: {}", s))?;
+        let spec = Self { format: format.parse()?, settings: settings.to_string() };
+        // 設定の誤りは、指定を読み込んだ時点で知らせる
+        CostProfile::default().set(&spec.settings)?;
+        Ok(spec)
+    }
+}
+
+impl std::fmt::Display for CostProfileSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.format, self.settings)
+    }
+}
+
+/// 型式ごとのコストの変換方法
+#[derive(Clone, Debug, Default)]
+pub struct CostProfiles {
+    profiles: Vec<(DictFormat, CostProfile)>,
+}
+
+impl CostProfiles {
+    /// 指定を順に適用する。同じ型式を複数回指定した場合は、後の指定で上書きする。
+    pub fn from_specs(specs: &[CostProfileSpec]) -> Result<Self, String> {
+        let mut profiles = Self::default();
+        for spec in specs {
+            let mut profile = profiles.get(spec.format);
+            profile.set(&spec.settings)?;
+            match profiles.profiles.iter_mut().find(|(format, _)| *format == spec.format) {
+                Some((_, p)) => *p = profile,
+                None => profiles.profiles.push((spec.format, profile)),
+            }
+        }
+        Ok(profiles)
+    }
+
+    /// 型式のコストの変換方法(指定がなければ既定値)
+    pub fn get(&self, format: DictFormat) -> CostProfile {
+        self.profiles.iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, p)| *p)
+            .unwrap_or_default()
+    }
+}
+
+/// Mozcのシステム辞書のコストを元に、エントリーのコストを決める(--cost-reference)
+/// 同じ表記のエントリーがあれば、読みが異なっていてもその最小のコストを用いる。
+/// なければ、同じ品詞IDのエントリーのコストの中央値を用いる。
+#[derive(Clone, Debug, Default)]
+pub struct CostReference {
+    surfaces: HashMap<String, i32>,
+    medians: HashMap<i32, i32>,
+}
+
+/// --cost-referenceでコストを決めた件数
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReferenceCounts {
+    /// 同じ表記のコストを用いた件数
+    pub surface: usize,
+    /// 同じ品詞IDのコストの中央値を用いた件数
+    pub hinshi_id: usize,
+    /// 参照できるコストがなく、--cost-profileの変換のままの件数
+    pub fallback: usize,
+}
+
+impl CostReference {
+    pub fn new(baseline: &Baseline) -> Self {
+        let mut surfaces: HashMap<String, i32> = HashMap::new();
+        let mut by_id: HashMap<i32, Vec<i32>> = HashMap::new();
+        for ((_, surface), entries) in baseline.iter() {
+            for entry in entries {
+                surfaces.entry(surface.clone())
+                    .and_modify(|cost| *cost = (*cost).min(entry.cost))
+                    .or_insert(entry.cost);
+                by_id.entry(entry.id).or_default().push(entry.cost);
+            }
+        }
+        let medians = by_id.into_iter()
+            .map(|(id, mut costs)| {
+                costs.sort_unstable();
+                (id, costs[(costs.len() - 1) / 2])
+            })
+            .collect();
+        Self { surfaces, medians }
+    }
+
+    /// 表記と品詞IDから、参照するコストを求める
+    /// 同じ表記のコストを用いた場合はtrueを併せて返す。
+    pub fn cost(&self, surface: &str, hinshi_id: i32) -> Option<(i32, bool)> {
+        if let Some(&cost) = self.surfaces.get(surface) {
+            Some((cost, true))
+        } else {
+            self.medians.get(&hinshi_id).map(|&cost| (cost, false))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_rank_ties_together() {
+        assert_eq!(percentiles(&[30, 10, 20, 20, 40]), [0.75, 0.0, 0.375, 0.375, 1.0]);
+        assert_eq!(percentiles(&[5, 5, 5]), [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn percentiles_of_a_single_entry_is_the_middle() {
+        assert_eq!(percentiles(&[100]), [0.5]);
+        assert_eq!(percentiles(&[]), Vec::<f64>::new());
+        assert_eq!(percentile(&[], 100), 0.5);
+    }
+
+    #[test]
+    fn percentile_of_a_missing_cost_is_between_neighbours() {
+        assert_eq!(percentile(&[10, 20, 30], 15), 0.25);
+        assert_eq!(percentile(&[10, 20, 30], 0), 0.0);
+        assert_eq!(percentile(&[10, 20, 30], 99), 1.0);
+    }
+
+    #[test]
+    fn default_profile_maps_costs_linearly() {
+        let profile = CostProfile::default();
+        let expected = [8000, 8000, 6000, 6010, 6100, 6300, 6500, 7000, 10000, 10000];
+        assert_eq!(SAMPLE_COSTS.map(|cost| profile.apply(cost)), expected);
+        assert_eq!(profile.apply(9), 6000);
+        assert_eq!(profile.apply(10001), 10000);
+    }
+
+    #[test]
+    fn quantile_defaults_to_a_band_around_the_user_cost() {
+        let mut profile = CostProfile::default();
+        profile.set("curve=quantile").unwrap();
+        assert_eq!((profile.min, profile.max), DEFAULT_QUANTILE_BAND);
+        assert_eq!(profile.quantile(0.0), MOZC_USER_COST - 1500);
+        assert_eq!(profile.quantile(0.5), MOZC_USER_COST);
+
+        let mut profile = CostProfile::default();
+        profile.set("curve=quantile,min=3000,max=9000").unwrap();
+        assert_eq!((profile.quantile(0.0), profile.quantile(1.0)), (3000, 9000));
+
+        // 範囲を明示すれば、既定の範囲と同じでもそのまま用いる
+        let mut profile = CostProfile::default();
+        profile.set("curve=quantile,min=0,max=10000").unwrap();
+        assert_eq!((profile.min, profile.max), (0, 10000));
+    }
+}
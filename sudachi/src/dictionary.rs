@@ -0,0 +1,591 @@
+//! 変換結果の辞書データと、その出力
+
+use std::io::{Result as ioResult, Write};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::baseline::{Baseline, BaselineMatch};
+use crate::cost::{CostProfile, CostReference, ReferenceCounts, percentile};
+use crate::error::Error;
+use crate::output::{Compression, OutputFile};
+
+/// Mozcのユーザー辞書に、ひとつの辞書として取り込めるエントリー数の上限
+pub const MAX_USER_DICT_ENTRIES: usize = 1_000_000;
+
+/// 結果構造体
+/// yomi,surface,hinshi_idの組み合わせで重複チェックされる。
+#[derive(Hash, Eq, PartialEq, Clone)]
+pub struct DictionaryKey {
+    pub yomi: String,
+    pub surface: String,
+    pub hinshi_id: i32,
+}
+
+/// コストと品詞判定で判明した品詞の文字列
+/// priorityは入力元の優先順位で、値が小さいほど優先される。
+/// lineは入力元での行番号。
+pub struct DictionaryEntry {
+    pub key: DictionaryKey,
+    pub cost: i32,
+    /// 入力元のコスト(変換前)
+    pub raw_cost: i32,
+    pub pos: String,
+    pub priority: usize,
+    pub line: u64,
+}
+
+/// 出力時の並び順
+/// 同じ入力とid.defからは、常に同じ順序で出力される。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// 読み、品詞ID、コスト、表記の順
+    #[default]
+    Yomi,
+    /// 品詞ID、読み、コスト、表記の順
+    Id,
+    /// コスト、読み、品詞ID、表記の順
+    Cost,
+    /// 表記、読み、品詞ID、コストの順
+    Surface,
+    /// システム辞書型式の行のバイト順(LC_ALL=C sort相当)で並べる
+    Mozc,
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yomi" => Ok(SortOrder::Yomi),
+            "id" => Ok(SortOrder::Id),
+            "cost" => Ok(SortOrder::Cost),
+            "surface" => Ok(SortOrder::Surface),
+            "mozc" => Ok(SortOrder::Mozc),
+            _ => Err(format!("unknown sort order: {} (yomi, id, cost, surface, mozc)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SortOrder::Yomi => "yomi",
+            SortOrder::Id => "id",
+            SortOrder::Cost => "cost",
+            SortOrder::Surface => "surface",
+            SortOrder::Mozc => "mozc",
+        })
+    }
+}
+
+impl SortOrder {
+    /// 指定された並び順で2つのエントリーを比較する
+    pub fn compare(&self, a: &DictionaryEntry, b: &DictionaryEntry) -> Ordering {
+        let (ka, kb) = (&a.key, &b.key);
+        let order = match self {
+            SortOrder::Yomi => ka.yomi.cmp(&kb.yomi)
+                .then(ka.hinshi_id.cmp(&kb.hinshi_id))
+                .then(a.cost.cmp(&b.cost))
+                .then(ka.surface.cmp(&kb.surface)),
+            SortOrder::Id => ka.hinshi_id.cmp(&kb.hinshi_id)
+                .then(ka.yomi.cmp(&kb.yomi))
+                .then(a.cost.cmp(&b.cost))
+                .then(ka.surface.cmp(&kb.surface)),
+            SortOrder::Cost => a.cost.cmp(&b.cost)
+                .then(ka.yomi.cmp(&kb.yomi))
+                .then(ka.hinshi_id.cmp(&kb.hinshi_id))
+                .then(ka.surface.cmp(&kb.surface)),
+            SortOrder::Surface => ka.surface.cmp(&kb.surface)
+                .then(ka.yomi.cmp(&kb.yomi))
+                .then(ka.hinshi_id.cmp(&kb.hinshi_id))
+                .then(a.cost.cmp(&b.cost)),
+            SortOrder::Mozc => ka.yomi.cmp(&kb.yomi)
+                .then(ka.hinshi_id.to_string().cmp(&kb.hinshi_id.to_string()))
+                .then(a.cost.to_string().cmp(&b.cost.to_string()))
+                .then(ka.surface.cmp(&kb.surface)),
+        };
+        // キーが同じでも順序が揺れないように、最後に品詞の文字列を比較する
+        order.then(a.pos.cmp(&b.pos))
+    }
+}
+
+/// 同じキーのエントリーが複数ある場合に、どちらを残すか
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// 優先順位の高い入力元のもの。同じ入力元なら先に追加されたもの
+    #[default]
+    Priority,
+    /// 入力元に関わらず、先に追加されたもの
+    First,
+    /// コストの小さいもの。同じコストならPriorityと同じ
+    MinCost,
+    /// コストの大きいもの。同じコストならPriorityと同じ
+    MaxCost,
+}
+
+impl std::str::FromStr for CollisionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "priority" => Ok(CollisionPolicy::Priority),
+            "first" => Ok(CollisionPolicy::First),
+            "min-cost" => Ok(CollisionPolicy::MinCost),
+            "max-cost" => Ok(CollisionPolicy::MaxCost),
+            _ => Err(format!("unknown collision policy: {} (priority, first, min-cost, max-cost)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for CollisionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CollisionPolicy::Priority => "priority",
+            CollisionPolicy::First => "first",
+            CollisionPolicy::MinCost => "min-cost",
+            CollisionPolicy::MaxCost => "max-cost",
+        })
+    }
+}
+
+impl CollisionPolicy {
+    /// 既にあるエントリーを、新しいエントリーで置き換えるか
+    pub fn replaces(&self, existing: &DictionaryEntry, entry: &DictionaryEntry) -> bool {
+        let by_priority = entry.priority < existing.priority;
+        match self {
+            CollisionPolicy::Priority => by_priority,
+            CollisionPolicy::First => false,
+            CollisionPolicy::MinCost => entry.cost < existing.cost || (entry.cost == existing.cost && by_priority),
+            CollisionPolicy::MaxCost => entry.cost > existing.cost || (entry.cost == existing.cost && by_priority),
+        }
+    }
+}
+
+/// 重複したキーの集計
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CollisionStats {
+    /// 重複したキーの件数
+    pub resolved: usize,
+    /// そのうち、後から追加されたエントリーで置き換えた件数
+    pub replaced: usize,
+}
+
+/// システム辞書型式とユーザー辞書型式
+pub struct DictionaryData {
+    entries: HashMap<DictionaryKey, DictionaryEntry>,
+    user_entries: HashMap<DictionaryKey, DictionaryEntry>,
+    collision_policy: CollisionPolicy,
+    collisions: CollisionStats,
+    // 入力元ごと、品詞IDごとの、追加されたエントリーの元のコスト
+    // 重複で残らなかったエントリーも含め、入力元のコストの分布(quantile_costs)に用いる。
+    raw_costs: HashMap<usize, HashMap<i32, Vec<i32>>>,
+}
+
+impl Default for DictionaryData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DictionaryData {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            user_entries: HashMap::new(),
+            collision_policy: CollisionPolicy::default(),
+            collisions: CollisionStats::default(),
+            raw_costs: HashMap::new(),
+        }
+    }
+
+    /// 同じキーのエントリーが複数ある場合に、どちらを残すかを指定する
+    pub fn set_collision_policy(&mut self, policy: CollisionPolicy) {
+        self.collision_policy = policy;
+    }
+
+    pub fn collision_policy(&self) -> CollisionPolicy {
+        self.collision_policy
+    }
+
+    /// これまでに重複したキーの集計
+    pub fn collisions(&self) -> CollisionStats {
+        self.collisions
+    }
+
+    /// 同じキーのエントリーが既にある場合、collision_policyに従ってどちらかを残す。
+    /// 既定では優先順位の高い入力元のものを残し、優先順位が同じ場合は、先に追加されたものを残す。
+    /// 残らなかった方のエントリーを返す。
+    pub fn add(&mut self, entry: DictionaryEntry, is_user_dict: bool) -> Option<DictionaryEntry> {
+        self.raw_costs.entry(entry.priority).or_default()
+            .entry(entry.key.hinshi_id).or_default()
+            .push(entry.raw_cost);
+        let target = if is_user_dict { &mut self.user_entries } else { &mut self.entries };
+        match target.entry(entry.key.clone()) {
+            Entry::Occupied(mut e) => {
+                self.collisions.resolved += 1;
+                if self.collision_policy.replaces(e.get(), &entry) {
+                    self.collisions.replaced += 1;
+                    Some(e.insert(entry))
+                } else {
+                    Some(entry)
+                }
+            },
+            Entry::Vacant(e) => {
+                e.insert(entry);
+                None
+            },
+        }
+    }
+
+    /// 入力元のエントリーのコストを、品詞の分類ごとの元のコストの百分位から決め直す
+    /// 百分位は、重複で残らなかったものも含めた、入力元から追加されたすべてのエントリーの分布から求める。
+    /// classesは品詞IDと品詞の分類の対応。変換後のコストの範囲は、profileのminとmax。
+    /// 決め直したエントリーの件数を返す。
+    pub fn quantile_costs(&mut self, priority: usize, profile: &CostProfile, classes: &HashMap<i32, String>) -> usize {
+        let class = |hinshi_id: i32| classes.get(&hinshi_id).map(|c| c.as_str()).unwrap_or("");
+        let mut by_class: HashMap<&str, Vec<i32>> = HashMap::new();
+        for (hinshi_id, costs) in self.raw_costs.remove(&priority).unwrap_or_default() {
+            by_class.entry(class(hinshi_id)).or_default().extend(costs);
+        }
+        for costs in by_class.values_mut() {
+            costs.sort_unstable();
+        }
+        let mut count = 0;
+        for entry in self.entries.values_mut().chain(self.user_entries.values_mut()).filter(|e| e.priority == priority) {
+            if let Some(sorted) = by_class.get(class(entry.key.hinshi_id)) {
+                entry.cost = profile.quantile(percentile(sorted, entry.raw_cost));
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Mozcのシステム辞書のコストを元に、エントリーのコストを決め直す
+    /// 参照できるコストがないエントリーは、そのままのコストとする。
+    pub fn reference_costs(&mut self, reference: &CostReference) -> ReferenceCounts {
+        let mut counts = ReferenceCounts::default();
+        for entry in self.entries.values_mut().chain(self.user_entries.values_mut()) {
+            match reference.cost(&entry.key.surface, entry.key.hinshi_id) {
+                Some((cost, by_surface)) => {
+                    entry.cost = cost;
+                    if by_surface {
+                        counts.surface += 1;
+                    } else {
+                        counts.hinshi_id += 1;
+                    }
+                },
+                None => counts.fallback += 1,
+            }
+        }
+        counts
+    }
+
+    /// システム辞書にある読みのエントリーのコストを、その読みの最良の候補のコストにmarginを加えた値以上にする
+    /// 生成した単語が、Mozcの候補より上位に来ないようにするため。コストを上げたエントリーを返す。
+    pub fn raise_cost_floor(&mut self, baseline: &Baseline, margin: i32) -> Vec<&DictionaryEntry> {
+        let floors = baseline.min_costs_by_yomi();
+        let mut raised = Vec::new();
+        for entry in self.entries.values_mut().chain(self.user_entries.values_mut()) {
+            if let Some(&best) = floors.get(entry.key.yomi.as_str()) {
+                let floor = best.saturating_add(margin);
+                if entry.cost < floor {
+                    entry.cost = floor;
+                    raised.push(&*entry);
+                }
+            }
+        }
+        raised
+    }
+
+    /// Mozcのシステム辞書に既にあるエントリーを取り除き、取り除いたエントリーを返す
+    /// システム辞書型式とユーザー辞書型式の両方から取り除く。
+    pub fn remove_baseline(&mut self, baseline: &Baseline, by: BaselineMatch) -> Vec<DictionaryEntry> {
+        let mut removed = Vec::new();
+        for map in [&mut self.entries, &mut self.user_entries] {
+            let keys: Vec<DictionaryKey> = map.keys()
+                .filter(|k| baseline.contains(&k.yomi, &k.surface, k.hinshi_id, by))
+                .cloned()
+                .collect();
+            removed.extend(keys.iter().filter_map(|k| map.remove(k)));
+        }
+        removed.sort_by(|a, b| SortOrder::Yomi.compare(a, b));
+        removed
+    }
+
+    /// システム辞書型式のエントリー
+    pub fn entries(&self) -> &HashMap<DictionaryKey, DictionaryEntry> {
+        &self.entries
+    }
+
+    /// ユーザー辞書型式のエントリー
+    pub fn user_entries(&self) -> &HashMap<DictionaryKey, DictionaryEntry> {
+        &self.user_entries
+    }
+
+    // HashMapの順序は実行ごとに変わるので、並べ替えてから出力する
+    fn sorted(map: &HashMap<DictionaryKey, DictionaryEntry>, order: SortOrder) -> Vec<&DictionaryEntry> {
+        let mut list: Vec<&DictionaryEntry> = map.values().collect();
+        list.sort_by(|a, b| order.compare(a, b));
+        list
+    }
+
+    /// 辞書データをwriterへ出力する。
+    /// user_dictがtrueの場合はユーザー辞書型式、falseの場合はシステム辞書型式で出力する。
+    pub fn output<W: Write>(&self, writer: &mut W, user_dict: bool, order: SortOrder) -> ioResult<()> {
+        // システム辞書のエントリーを出力
+        if ! user_dict {
+            for entry in Self::sorted(&self.entries, order) {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{}",
+                    entry.key.yomi, entry.key.hinshi_id, entry.key.hinshi_id, entry.cost, entry.key.surface
+                )?;
+            }
+        } else {
+            // -Uオプションが設定されている場合のみユーザー辞書を出力
+            for entry in self.user_output(order) {
+                write_user_entry(writer, entry)?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// ユーザー辞書型式で出力するエントリーを、orderの順に並べて返す
+    /// システム辞書型式にもあるエントリーは除く。
+    pub fn user_output(&self, order: SortOrder) -> Vec<&DictionaryEntry> {
+        let mut list = Self::sorted(&self.user_entries, order);
+        list.retain(|entry| !self.entries.contains_key(&entry.key));
+        list
+    }
+
+    /// ユーザー辞書型式で、dirへmax_entries件ずつに分けて出力する。
+    /// ファイル名は`user_dic-01.txt`からの連番で、書き出したファイルのパスと件数を返す。
+    /// 圧縮する場合は、ファイル名に`.xz`などの拡張子を付ける。
+    /// 同じ読みのエントリーは同じファイルに入れ、読みの変わり目で分ける。
+    /// 前回の出力で残った、より大きい番号のファイルは削除する。
+    pub fn output_chunks(&self, dir: &Path, max_entries: usize, order: SortOrder, compression: Compression) -> Result<Vec<(PathBuf, usize)>, Error> {
+        let config_error = |message: String| Error::Config { path: dir.to_path_buf(), message };
+        if max_entries == 0 {
+            return Err(config_error("--max-entries must be at least 1".to_string()));
+        }
+        let list = self.user_output(order);
+        let chunks = split_by_yomi(&list, max_entries).map_err(config_error)?;
+
+        std::fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        let mut written = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let path = chunk_path(dir, i + 1, compression);
+            let mut writer = OutputFile::create(&path, Some(compression))?;
+            for entry in chunk.iter() {
+                write_user_entry(&mut writer, entry).map_err(|e| Error::io(&path, e))?;
+            }
+            writer.finish().map_err(|e| Error::io(&path, e))?;
+            written.push((path, chunk.len()));
+        }
+        let mut stale = chunks.len() + 1;
+        while chunk_path(dir, stale, compression).exists() {
+            let path = chunk_path(dir, stale, compression);
+            std::fs::remove_file(&path).map_err(|e| Error::io(&path, e))?;
+            stale += 1;
+        }
+        Ok(written)
+    }
+}
+
+/// エントリーの並びを、max_entries件以下ずつに分ける
+/// 同じ読みのエントリーは、並び順にかかわらず同じ組に集め、最初に現れた位置に置く。max_entriesより多く同じ読みがある場合はエラーとする。
+/// 読み順(--sort yomi、mozc)では、元の並びのまま読みの変わり目で分けることになる。
+pub fn split_by_yomi<'a>(list: &[&'a DictionaryEntry], max_entries: usize) -> Result<Vec<Vec<&'a DictionaryEntry>>, String> {
+    let mut groups: Vec<Vec<&DictionaryEntry>> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for &entry in list {
+        let i = *index.entry(entry.key.yomi.as_str()).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[i].push(entry);
+    }
+
+    let mut chunks = Vec::new();
+    let mut chunk: Vec<&DictionaryEntry> = Vec::new();
+    for group in groups {
+        if group.len() > max_entries {
+            return Err(format!("more than {} entries have the reading {}", max_entries, group[0].key.yomi));
+        }
+        if chunk.len() + group.len() > max_entries {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        chunk.extend(group);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    Ok(chunks)
+}
+
+// 分割したユーザー辞書のファイル名(split --numeric-suffixes=1と同じく01から)
+fn chunk_path(dir: &Path, number: usize, compression: Compression) -> PathBuf {
+    dir.join(format!("user_dic-{:02}.txt{}", number, compression.suffix()))
+}
+
+// ユーザー辞書型式の1行
+fn write_user_entry<W: Write>(writer: &mut W, entry: &DictionaryEntry) -> ioResult<()> {
+    writeln!(
+        writer,
+        "{}\t{}\t{}\t",
+        entry.key.yomi, entry.key.surface, entry.pos
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(yomi: &str, surface: &str, cost: i32, priority: usize) -> DictionaryEntry {
+        DictionaryEntry {
+            key: DictionaryKey { yomi: yomi.to_string(), surface: surface.to_string(), hinshi_id: 1 },
+            cost,
+            raw_cost: cost,
+            pos: "名詞".to_string(),
+            priority,
+            line: 1,
+        }
+    }
+
+    fn yomi_sets(chunks: &[Vec<&DictionaryEntry>]) -> Vec<Vec<String>> {
+        chunks.iter().map(|chunk| {
+            let mut yomi: Vec<String> = chunk.iter().map(|e| e.key.yomi.clone()).collect();
+            yomi.dedup();
+            yomi
+        }).collect()
+    }
+
+    #[test]
+    fn split_keeps_each_reading_in_one_chunk() {
+        let mut dict_data = DictionaryData::new();
+        for (i, (yomi, surface)) in [("あ", "亜"), ("い", "井"), ("あ", "阿"), ("う", "宇"), ("い", "伊"), ("あ", "吾")].iter().enumerate() {
+            dict_data.add(entry(yomi, surface, 1000 * i as i32, 0), true);
+        }
+        for order in [SortOrder::Yomi, SortOrder::Id, SortOrder::Cost, SortOrder::Surface, SortOrder::Mozc] {
+            let list = dict_data.user_output(order);
+            let chunks = split_by_yomi(&list, 3).unwrap();
+            assert_eq!(chunks.iter().map(|c| c.len()).sum::<usize>(), 6, "sort {}", order);
+            assert!(chunks.iter().all(|c| c.len() <= 3), "sort {}", order);
+            let sets = yomi_sets(&chunks);
+            for yomi in ["あ", "い", "う"] {
+                let count = sets.iter().filter(|set| set.iter().any(|y| y == yomi)).count();
+                assert_eq!(count, 1, "sort {}: {} is in {} chunks", order, yomi, count);
+            }
+        }
+    }
+
+    #[test]
+    fn split_keeps_yomi_order() {
+        let list = [entry("あ", "亜", 0, 0), entry("あ", "阿", 0, 0), entry("い", "井", 0, 0), entry("う", "宇", 0, 0)];
+        let list: Vec<&DictionaryEntry> = list.iter().collect();
+        let chunks = split_by_yomi(&list, 2).unwrap();
+        assert_eq!(yomi_sets(&chunks), [vec!["あ"], vec!["い", "う"]]);
+    }
+
+    #[test]
+    fn split_rejects_a_reading_larger_than_a_chunk() {
+        let list = [entry("あ", "亜", 0, 0), entry("あ", "阿", 0, 0), entry("あ", "吾", 0, 0)];
+        let list: Vec<&DictionaryEntry> = list.iter().collect();
+        assert!(split_by_yomi(&list, 2).is_err());
+        assert_eq!(split_by_yomi(&list, 3).unwrap().len(), 1);
+    }
+
+    fn quantile_profile() -> CostProfile {
+        let mut profile = CostProfile::default();
+        profile.set("curve=quantile,min=1000,max=3000").unwrap();
+        profile
+    }
+
+    fn costs(dict_data: &DictionaryData) -> Vec<(String, i32)> {
+        let mut costs: Vec<(String, i32)> = dict_data.user_entries.values().map(|e| (e.key.surface.clone(), e.cost)).collect();
+        costs.sort();
+        costs
+    }
+
+    #[test]
+    fn quantile_costs_rank_within_each_class() {
+        let mut dict_data = DictionaryData::new();
+        let mut add = |surface: &str, hinshi_id: i32, raw_cost: i32| {
+            let mut e = entry(surface, surface, raw_cost, 0);
+            e.key.hinshi_id = hinshi_id;
+            dict_data.add(e, true);
+        };
+        // 品詞の分類「名詞」の3件(同じコストが2件)、「動詞」の1件、分類のない品詞IDの1件
+        add("a", 1, 100);
+        add("b", 1, 100);
+        add("c", 2, 500);
+        add("d", 3, 7);
+        add("e", 9, 42);
+        let classes = HashMap::from([(1, "名詞".to_string()), (2, "名詞".to_string()), (3, "動詞".to_string())]);
+        assert_eq!(dict_data.quantile_costs(0, &quantile_profile(), &classes), 5);
+        assert_eq!(costs(&dict_data), [
+            ("a".to_string(), 1500), ("b".to_string(), 1500), ("c".to_string(), 3000),
+            ("d".to_string(), 2000), ("e".to_string(), 2000),
+        ]);
+        // 一度決め直した入力元の分布は残らない
+        assert_eq!(dict_data.quantile_costs(1, &quantile_profile(), &classes), 0);
+    }
+
+    #[test]
+    fn quantile_costs_include_entries_lost_to_collisions() {
+        let mut dict_data = DictionaryData::new();
+        dict_data.add(entry("あ", "亜", 0, 0), true);
+        dict_data.add(entry("あ", "亜", 100, 1), true);
+        dict_data.add(entry("い", "井", 200, 1), true);
+        let classes = HashMap::new();
+        assert_eq!(dict_data.quantile_costs(1, &quantile_profile(), &classes), 1);
+        // 入力元1の分布は[100, 200]なので、「井」は最大のコストになる
+        assert_eq!(costs(&dict_data), [("井".to_string(), 3000), ("亜".to_string(), 0)]);
+    }
+
+    #[test]
+    fn collision_policy_decides_which_entry_stays() {
+        let early = entry("あ", "亜", 5000, 0);
+        let late_cheap = entry("あ", "亜", 4000, 1);
+        let late_costly = entry("あ", "亜", 6000, 1);
+        let late_same = entry("あ", "亜", 5000, 1);
+        let cases = [
+            (CollisionPolicy::Priority, [false, false, false]),
+            (CollisionPolicy::First, [false, false, false]),
+            (CollisionPolicy::MinCost, [true, false, false]),
+            (CollisionPolicy::MaxCost, [false, true, false]),
+        ];
+        for (policy, expected) in cases {
+            let replaced = [&late_cheap, &late_costly, &late_same].map(|e| policy.replaces(&early, e));
+            assert_eq!(replaced, expected, "{}", policy);
+        }
+        // 同じコストや、優先順位だけが異なる場合は、優先順位の高い入力元を残す
+        for policy in [CollisionPolicy::Priority, CollisionPolicy::MinCost, CollisionPolicy::MaxCost] {
+            assert!(policy.replaces(&late_same, &early), "{}", policy);
+        }
+        assert!(!CollisionPolicy::First.replaces(&late_same, &early));
+    }
+
+    #[test]
+    fn add_counts_collisions() {
+        let mut dict_data = DictionaryData::new();
+        dict_data.set_collision_policy(CollisionPolicy::MinCost);
+        assert!(dict_data.add(entry("あ", "亜", 5000, 0), true).is_none());
+        assert_eq!(dict_data.add(entry("あ", "亜", 4000, 1), true).map(|e| e.cost), Some(5000));
+        assert_eq!(dict_data.add(entry("あ", "亜", 4500, 0), true).map(|e| e.cost), Some(4500));
+        let stats = dict_data.collisions();
+        assert_eq!((stats.resolved, stats.replaced), (2, 1));
+        assert_eq!(costs(&dict_data), [("亜".to_string(), 4000)]);
+    }
+
+    #[test]
+    fn collision_policy_round_trips_through_its_name() {
+        for policy in [CollisionPolicy::Priority, CollisionPolicy::First, CollisionPolicy::MinCost, CollisionPolicy::MaxCost] {
+            assert_eq!(policy.to_string().parse::<CollisionPolicy>(), Ok(policy));
+        }
+        assert!("cheapest".parse::<CollisionPolicy>().is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! 読み込み時のエラー
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use csv::StringRecord;
+
+/// 辞書ファイルやid.defの読み込みで発生するエラー
+/// どのファイルの何行目で発生したのかを保持する。
+#[derive(Debug)]
+pub enum Error {
+    /// ファイルが開けない、読み込めない
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// CSVとして解釈できない行
+    Csv {
+        path: PathBuf,
+        line: Option<u64>,
+        source: csv::Error,
+    },
+    /// 列が足りない行
+    Columns {
+        path: PathBuf,
+        line: u64,
+        expected: usize,
+        found: usize,
+    },
+    /// 列の値が不正な行
+    Field {
+        path: PathBuf,
+        line: u64,
+        column: usize,
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+    /// 設定ファイルの内容が不正
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl Error {
+    pub fn io(path: &Path, source: std::io::Error) -> Self {
+        Error::Io { path: path.to_path_buf(), source }
+    }
+
+    pub fn csv(path: &Path, source: csv::Error) -> Self {
+        let line = source.position().map(|p| p.line());
+        Error::Csv { path: path.to_path_buf(), line, source }
+    }
+
+    /// 列の値が不正であることを示すエラーを作る
+    /// columnは0から数えた列番号
+    pub fn field(path: &Path, record: &StringRecord, column: usize, name: &'static str, reason: impl Into<String>) -> Self {
+        Error::Field {
+            path: path.to_path_buf(),
+            line: record_line(record),
+            column,
+            name,
+            value: record.get(column).unwrap_or_default().to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    /// エラーが発生したファイル
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::Io { path, .. }
+            | Error::Csv { path, .. }
+            | Error::Columns { path, .. }
+            | Error::Field { path, .. }
+            | Error::Config { path, .. } => path,
+        }
+    }
+
+    /// エラーが発生した行番号(1から数える)
+    pub fn line(&self) -> Option<u64> {
+        match self {
+            Error::Io { .. } | Error::Config { .. } => None,
+            Error::Csv { line, .. } => *line,
+            Error::Columns { line, .. } | Error::Field { line, .. } => Some(*line),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Csv { path, line: Some(line), source } => write!(f, "{}:{}: {}", path.display(), line, source),
+            Error::Csv { path, line: None, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Columns { path, line, expected, found } => write!(
+                f,
+                "{}:{}: expected at least {} columns, found {}",
+                path.display(), line, expected, found
+            ),
+            Error::Field { path, line, column, name, value, reason } => write!(
+                f,
+                "{}:{}: field {} (column {}): {}: {:?}",
+                path.display(), line, name, column + 1, reason, value
+            ),
+            Error::Config { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Csv { source, .. } => Some(source),
+            Error::Columns { .. } | Error::Field { .. } | Error::Config { .. } => None,
+        }
+    }
+}
+
+/// レコードの行番号(1から数える)
+pub(crate) fn record_line(record: &StringRecord) -> u64 {
+    record.position().map(|p| p.line()).unwrap_or(0)
+}
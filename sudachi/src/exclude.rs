@@ -0,0 +1,138 @@
+//! 除外する単語の一覧(exclude.txt)
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+
+use crate::error::Error;
+use crate::utils::config_dir;
+
+// lib/dictutils.rbのFile.fnmatch?と同じく、/や先頭の.も*で一致させる
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+/// 除外する単語のパターン
+pub struct ExcludePattern {
+    /// exclude.txtに書かれた行
+    pub text: String,
+    /// exclude.txtの行番号
+    pub line: usize,
+    yomi: Pattern,
+    surface: Pattern,
+    count: Cell<usize>,
+}
+
+impl ExcludePattern {
+    /// このパターンで除外されたエントリーの数
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+}
+
+/// 除外する単語の一覧
+/// 1行にひとつ、読みと表記のパターンを空白で区切って書く。パターンはfnmatchと同じく、`*`、`?`、`[...]`が使える。
+/// 読みと表記の両方が一致したエントリーは、辞書に追加されない。
+#[derive(Default)]
+pub struct ExcludeList {
+    patterns: Vec<ExcludePattern>,
+}
+
+impl ExcludeList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 既定の除外リストのパス
+    /// `$XDG_CONFIG_HOME/mozcdict-ext/exclude.txt`(XDG_CONFIG_HOMEがなければ`~/.config`)
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("exclude.txt"))
+    }
+
+    /// 除外リストを読み込む
+    /// 読みと表記が揃っていない行は、lib/dictutils.rbと同じく無視する。
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let mut patterns = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let (yomi, surface) = match line.split_once(char::is_whitespace) {
+                Some((yomi, surface)) if !surface.trim().is_empty() => (yomi, surface.trim()),
+                _ => continue,
+            };
+            let compile = |p: &str| Pattern::new(p).map_err(|e| Error::Config {
+                path: path.to_path_buf(),
+                message: format!("line {}: invalid pattern {:?}: {}", i + 1, p, e.msg),
+            });
+            patterns.push(ExcludePattern {
+                text: line.to_string(),
+                line: i + 1,
+                yomi: compile(yomi)?,
+                surface: compile(surface)?,
+                count: Cell::new(0),
+            });
+        }
+        Ok(Self { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// 読みと表記が、いずれかのパターンに一致するか
+    /// 最初に一致したパターンの件数を数える。
+    pub fn matches(&self, yomi: &str, surface: &str) -> bool {
+        let found = self.patterns.iter().find(|p| {
+            p.yomi.matches_with(yomi, MATCH_OPTIONS) && p.surface.matches_with(surface, MATCH_OPTIONS)
+        });
+        if let Some(p) = found {
+            p.count.set(p.count.get() + 1);
+        }
+        found.is_some()
+    }
+
+    /// 全てのパターン
+    pub fn patterns(&self) -> &[ExcludePattern] {
+        &self.patterns
+    }
+
+    /// パターンごとの除外件数を標準エラー出力へ表示する
+    pub fn print_summary(&self) {
+        for p in &self.patterns {
+            eprintln!("excluded {}: {}", p.text, p.count());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_file;
+
+    fn load(text: &str) -> Result<ExcludeList, Error> {
+        let path = temp_file("exclude.txt", text);
+        let result = ExcludeList::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn matches_yomi_and_surface_and_counts_the_first_pattern() {
+        let list = load("てすと テスト\nてす* *\nひとつだけ\n").unwrap();
+        assert_eq!(list.patterns().iter().map(|p| p.line).collect::<Vec<_>>(), [1, 2]);
+        assert!(list.matches("てすと", "テスト"));
+        assert!(list.matches("てすと", "手酢戸"));
+        assert!(list.matches("てすら", "a/.b"));
+        assert!(!list.matches("てと", "テスト"));
+        assert_eq!(list.patterns().iter().map(|p| p.count()).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn invalid_pattern_is_an_error() {
+        let err = load("[あ テスト\n").err().unwrap();
+        assert!(err.to_string().contains("line 1: invalid pattern"), "{}", err);
+    }
+}
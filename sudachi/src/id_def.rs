@@ -0,0 +1,313 @@
+//! Mozcのid.defの読み込みと、品詞の判定
+
+use std::fs::File;
+use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+
+use csv::ReaderBuilder;
+use lazy_regex::regex;
+
+use crate::error::{Error, record_line};
+use crate::pos_explain::PosExplain;
+use crate::pos_mapping::{PosMapping, create_pos_mapping, u_search_key};
+use crate::pos_override::PosOverrides;
+
+/// Mozc ソースに含まれるsrc/data/dictionary_oss/id.def
+/// 更新される可能性がある。
+/// 同じ入力からは常に同じ結果になるよう、品詞の文字列の順に並べて保持する。
+pub type IdDef = BTreeMap<String, i32>;
+
+// 動詞の活用型
+const VERB_TYPES: [&str; 6] = ["五段", "四段", "一段", "カ変", "サ変", "ラ変"];
+
+// 動詞の活用型が、id.defの品詞の活用型と一致するか
+pub(crate) fn verb_type_matches(verb_type: &str, key_parts: &[&str]) -> bool {
+    VERB_TYPES.iter().any(|t| verb_type.contains(t) && key_parts.iter().any(|k| k.contains(t)))
+}
+
+/// 品詞判定の候補
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PosCandidate {
+    pub id: i32,
+    /// 一致した項目の数を元にした点数
+    pub score: i32,
+    /// 候補となったid.defの品詞
+    pub expr: String,
+}
+
+/// 辞書データの品詞情報とid.defを比較して、品詞の候補を点数の高い順に返す。
+/// 点数が同じ場合は、IDの小さい順に並べる。
+pub fn id_candidates(clsexpr: &str, id_def: &IdDef) -> Vec<PosCandidate> {
+    let expr: Vec<&str> = clsexpr.split(',').collect();
+    let mut candidates = Vec::new();
+
+    for (key, &id) in id_def.iter() {
+        let key_parts: Vec<&str> = key.split(',').collect();
+
+        // 品詞の主要部分(最初の2-3項目)が一致するかを確認
+        if expr.len() >= 2 && key_parts.len() >= 2 &&
+            expr[0] == key_parts[0] && expr[1] == key_parts[1] {
+
+                let mut match_count = 2; // 最初の2項目は既に一致している
+                let mut is_valid_match = true;
+
+                // 残りの項目をチェック
+                for (i, (a, b)) in expr.iter().zip(key_parts.iter()).skip(2).enumerate() {
+                    if *b != "*" && *a == *b {
+                        match_count += 1;
+                    } else if i < 1 { // 3番目の項目（小分類）まで厳密にチェック
+                        is_valid_match = false;
+                        break;
+                    } else {
+                        // 4番目以降の項目は部分一致も許容
+                        if a.contains(b) || b.contains(a) {
+                            match_count += 1;
+                        }
+                        break; // 最初の不一致で終了
+                    }
+                }
+
+                // 特殊なケースの処理
+                if expr[0] == "名詞" && expr[1] == "固有名詞" {
+                    if match_count < 3 { // 固有名詞の場合、より詳細なマッチングを要求
+                        is_valid_match = false;
+                    }
+                } else if expr[0] == "動詞" {
+                    // 動詞の活用型のチェック
+                    let verb_type = expr.get(4).unwrap_or(&"");
+                    if verb_type_matches(verb_type, &key_parts) {
+                        match_count += 1;
+                    }
+                }
+
+                if is_valid_match {
+                    candidates.push(PosCandidate { id, score: match_count, expr: key.to_string() });
+                }
+            }
+    }
+
+    // HashMapの順序に依存しないように、点数が同じ場合はIDと品詞の文字列で並べる
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then(a.id.cmp(&b.id)).then(a.expr.cmp(&b.expr)));
+    candidates
+}
+
+/// 辞書データの品詞情報とid.defを比較して品詞のidを確定する。
+/// 判定結果はid_defとclass_mapに追加され、判定できなかった場合はdefault_noun_idを返す。
+pub fn id_expr(clsexpr: &str, id_def: &mut IdDef, class_map: &mut HashMap<String, i32>, default_noun_id: i32) -> i32 {
+    if let Some(&r) = id_def.get(clsexpr) {
+        class_map.insert(clsexpr.to_string(), r);
+        return r;
+    }
+
+    let result_id = id_candidates(clsexpr, id_def).first().map(|c| c.id).unwrap_or(default_noun_id);
+    id_def.insert(clsexpr.to_string(), result_id);
+    class_map.insert(clsexpr.to_string(), result_id);
+    result_id
+}
+
+/// id.defと、品詞判定の結果をまとめたもの
+/// 同じ品詞の文字列は、一度だけ判定される。
+pub struct PosResolver {
+    pub id_def: IdDef,
+    /// 読み込んだままのid.def(判定の途中で追加された品詞を含まない)
+    lines: IdDef,
+    /// 品詞判定が出来なかった場合に用いる、普通名詞のID
+    pub default_noun_id: i32,
+    /// ユーザー辞書の品詞との対応表
+    pub mapping: PosMapping,
+    /// 点数による判定より優先する、品詞の対応表
+    overrides: PosOverrides,
+    class_map: HashMap<String, i32>,
+    user_pos_ids: HashMap<String, Option<i32>>,
+    explain: Option<PosExplain>,
+}
+
+impl PosResolver {
+    pub fn new(id_def: IdDef, default_noun_id: i32) -> Self {
+        Self {
+            lines: id_def.clone(),
+            id_def,
+            default_noun_id,
+            mapping: create_pos_mapping(),
+            overrides: PosOverrides::new(),
+            class_map: HashMap::new(),
+            user_pos_ids: HashMap::new(),
+            explain: None,
+        }
+    }
+
+    /// id.defを読み込む
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let (id_def, default_noun_id) = read_id_def(path)?;
+        Ok(Self::new(id_def, default_noun_id))
+    }
+
+    /// ユーザー辞書の品詞との対応表をファイルから読み込む
+    pub fn load_mapping(&mut self, path: &Path) -> Result<(), Error> {
+        self.mapping = PosMapping::load(path, &self.id_def)?;
+        Ok(())
+    }
+
+    /// 辞書データの品詞からid.defの品詞への対応表をファイルから読み込む
+    /// 対応表にある品詞は、点数による判定より優先される。
+    pub fn load_overrides(&mut self, path: &Path) -> Result<(), Error> {
+        let overrides = PosOverrides::load(path, &self.id_def)?;
+        self.set_overrides(overrides);
+        Ok(())
+    }
+
+    /// 辞書データの品詞からid.defの品詞への対応表を設定する
+    pub fn set_overrides(&mut self, overrides: PosOverrides) {
+        self.overrides = overrides;
+        if let Some(explain) = &mut self.explain {
+            explain.set_overrides(&self.overrides);
+        }
+    }
+
+    /// 品詞IDに対応する、id.defの品詞の文字列
+    /// 判定の途中で追加された品詞ではなく、読み込んだままのid.defの行から探すので、先に読み込んだ入力元によらない。
+    pub fn id_def_pos(&self, hinshi_id: i32) -> String {
+        search_key(&self.lines, hinshi_id)
+    }
+
+    /// 品詞IDに対応するユーザー辞書の品詞名
+    pub fn user_pos(&mut self, hinshi_id: i32) -> Option<String> {
+        u_search_key(&mut self.mapping, &mut self.lines, hinshi_id)
+    }
+
+    /// ユーザー辞書の品詞名に対応する品詞ID
+    /// 対応表にない品詞名(短縮よみ、抑制単語など)はNone
+    pub fn user_pos_id(&mut self, user_pos: &str) -> Option<i32> {
+        if let Some(&id) = self.user_pos_ids.get(user_pos) {
+            return id;
+        }
+        let id = self.mapping.user_pos_to_id(user_pos, &self.lines);
+        self.user_pos_ids.insert(user_pos.to_string(), id);
+        id
+    }
+
+    /// 品詞判定の詳細を記録する
+    pub fn enable_explain(&mut self) {
+        let mut explain = PosExplain::new(&self.id_def);
+        explain.set_overrides(&self.overrides);
+        self.explain = Some(explain);
+    }
+
+    /// 記録された品詞判定の詳細
+    pub fn explain(&self) -> Option<&PosExplain> {
+        self.explain.as_ref()
+    }
+
+    /// 品詞の文字列から品詞IDを判定する
+    /// 対応表(--pos-map)にあればそれを用い、なければid.defと比較して判定する。
+    pub fn resolve(&mut self, clsexpr: &str) -> i32 {
+        let id = match (self.class_map.get(clsexpr), self.overrides.get(clsexpr)) {
+            (Some(&id), _) => id,
+            (None, Some(rule)) => {
+                if let Some(explain) = &mut self.explain {
+                    explain.add_override(clsexpr, rule.id);
+                }
+                self.class_map.insert(clsexpr.to_string(), rule.id);
+                rule.id
+            },
+            (None, None) => {
+                if let Some(explain) = &mut self.explain {
+                    explain.add(clsexpr, &self.id_def, self.default_noun_id);
+                }
+                id_expr(clsexpr, &mut self.id_def, &mut self.class_map, self.default_noun_id)
+            }
+        };
+        if let Some(explain) = &mut self.explain {
+            explain.count(clsexpr);
+        }
+        id
+    }
+}
+
+/// id.defの品詞の文字列を、辞書データの品詞と比較しやすい形に置き換える
+pub(crate) fn normalize_id_def_pos(pos: &str) -> String {
+    let mut expr = pos.replace("名詞,一般", "名詞,普通名詞")
+        .replace("名詞,数,", "名詞,数詞,")
+        .replace("形-","形,")
+        .replace("地域,","地名,");
+
+    expr = regex!(r"五段・カ行[^,]*").replace(&expr, "五段・カ行").to_string();
+
+    for (re, head) in [(regex!(r"サ変([^,]*)"), "サ変,"), (regex!(r"ラ行([^,]*)"), "ラ行,"), (regex!(r"ワ行([^,]*)"), "ワ行,")] {
+        let cap = match re.captures(&expr) {
+            Some(i) => i.get(1).unwrap().as_str(),
+            None => "",
+        };
+        if !cap.is_empty() {
+            let mut s1 = String::from(head);
+            s1.push_str(cap);
+            expr = re.replace(&expr, s1).to_string();
+        };
+    }
+    expr
+}
+
+/// UniDic系の品詞(品詞大分類、中分類、小分類、細分類、活用型、活用形の6項目)を、id.defと比較する品詞の文字列にする
+pub(crate) fn normalize_unidic_pos(fields: &[&str]) -> String {
+    let s3 = fields[0].replace("補助記号", "記号"); //.replace("空白","記号");
+    let s4 = fields[1].replace("非自立可能","非自立"); //.replace(r"^数詞$", "数");
+    let s5 = fields[4].replace("下一段","一段").replace("一段-","一段,").replace("段-","段・");
+    let s6 = fields[5].replace("形-", "形,");
+    format!("{},{},{},{},{},{}", s3, s4, fields[2], fields[3], s5, s6)
+}
+
+/// id.defは更新されうるので、毎回、最新のものを読み込む。
+/// 品詞判定が出来なかった場合、普通名詞とみなす。
+/// default_noun_idは、その普通名詞のIDを格納しておく。
+/// id.defの不正な行は、常にエラーとする。
+pub fn read_id_def(path: &Path) -> Result<(IdDef, i32), Error> {
+    let mut hash = IdDef::new();
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b' ')
+        .from_reader(file);
+    let mut default_noun_id: i32 = -1;
+
+    for result in reader.records() {
+        let record = result.map_err(|e| Error::csv(path, e))?;
+        if record.len() < 2 {
+            return Err(Error::Columns { path: path.to_path_buf(), line: record_line(&record), expected: 2, found: record.len() });
+        }
+        let id: i32 = record[0].parse()
+            .map_err(|_| Error::field(path, &record, 0, "id", "not a number"))?;
+        let expr = normalize_id_def_pos(&record[1]);
+
+        // 名詞、一般名詞のIDを保存
+        if expr == "名詞,普通名詞,*,*,*,*,*" || expr == "名詞,一般,*,*,*,*,*" {
+            default_noun_id = id;
+        }
+
+        hash.insert(expr, id);
+    }
+    Ok((hash, default_noun_id))
+}
+
+/// 品詞IDごとの、品詞の分類(品詞の文字列の先頭から2項目。`名詞,固有名詞`など)
+/// ひとつの品詞IDに複数の品詞の文字列がある場合は、辞書順で最初のものを用いる。
+pub fn pos_classes(def: &IdDef) -> HashMap<i32, String> {
+    let mut classes: HashMap<i32, &str> = HashMap::new();
+    for (pos, &id) in def {
+        let first = classes.entry(id).or_insert(pos);
+        if pos.as_str() < *first {
+            *first = pos;
+        }
+    }
+    classes.into_iter()
+        .map(|(id, pos)| (id, pos.split(',').take(2).collect::<Vec<_>>().join(",")))
+        .collect()
+}
+
+/// id.defからキーを検索
+/// ひとつの品詞IDに複数の品詞の文字列がある場合は、辞書順で最初のものを返す。
+pub fn search_key(def: &IdDef, search: i32) -> String {
+    def.iter()
+        .find(|(_, &value)| value == search)
+        .map(|(key, _)| key.to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,58 @@
+//! SudachiDictなどの辞書データを、Mozcのシステム辞書型式およびユーザー辞書型式へ変換するライブラリ
+//!
+//! 変換は次の順で行う。
+//! 1. [`PosResolver::load`]でMozcのid.defを読み込む。
+//! 2. [`read_dictionary`]で辞書ファイルを読み込み、品詞を判定して[`DictionaryData`]へ追加する。
+//!    品詞の判定は[`PosResolver::resolve`]([`PosOverrides`]、[`id_expr`])、ユーザー辞書の品詞名は[`PosMapping`]で行う。
+//! 3. [`DictionaryData::output`]で出力する。
+//!
+//! ```no_run
+//! use std::io::stdout;
+//! use std::path::PathBuf;
+//! use dict_to_mozc::{read_dictionary, PosResolver, DictionaryData, DictFormat, InputSpec, ReadOptions, Rejects, SortOrder};
+//!
+//! let mut resolver = PosResolver::load("id.def".as_ref()).unwrap();
+//! let mut dict_data = DictionaryData::new();
+//! let input = InputSpec { format: DictFormat::Sudachi, path: PathBuf::from("core_lex.csv") };
+//! let opts = ReadOptions::default();
+//! read_dictionary(&input, &mut resolver, &mut dict_data, &opts, &mut Rejects::new()).unwrap();
+//! dict_data.output(&mut stdout(), false, SortOrder::Yomi).unwrap();
+//! ```
+
+pub mod baseline;
+pub mod byhand;
+pub mod cost;
+pub mod dictionary;
+pub mod error;
+pub mod exclude;
+pub mod id_def;
+pub mod output;
+pub mod pos_explain;
+pub mod pos_mapping;
+pub mod pos_override;
+pub mod reader;
+pub mod reject;
+pub mod user_db;
+pub mod utils;
+pub mod word_policy;
+
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod test_util;
+
+pub use crate::baseline::{Baseline, BaselineEntry, BaselineMatch};
+pub use crate::byhand::{ByhandDef, ByhandPos, ByhandRule};
+pub use crate::cost::{DEFAULT_COST_FLOOR_MARGIN, DEFAULT_QUANTILE_BAND, CostCurve, CostProfile, CostProfileSpec, CostProfiles, CostReference, ReferenceCounts};
+pub use crate::dictionary::{MAX_USER_DICT_ENTRIES, CollisionPolicy, CollisionStats, DictionaryData, DictionaryEntry, DictionaryKey, SortOrder};
+pub use crate::error::Error;
+pub use crate::exclude::{ExcludeList, ExcludePattern};
+pub use crate::id_def::{IdDef, PosCandidate, PosResolver, id_candidates, id_expr, pos_classes, read_id_def, search_key};
+pub use crate::output::{Compression, OutputFile};
+pub use crate::pos_explain::{PosExplain, PosExplanation, PosMatch};
+pub use crate::pos_mapping::{MOZC_USER_POS, PosMapping, create_pos_mapping, get_user_pos_by_id, u_search_key};
+pub use crate::pos_override::{PosOverride, PosOverrides};
+pub use crate::reader::{DictFormat, InputEncoding, InputSpec, PriorityOrder, ReadOptions, ReadStats, read_dictionary};
+pub use crate::reject::{RejectReason, Rejects};
+pub use crate::user_db::{UserDictionary, UserDictionaryEntry, UserDictionaryStorage};
+pub use crate::word_policy::WordPolicy;
+pub use crate::reader::{byhand_read_csv, ipadic_read_csv, mozc_user_read_csv, neologd_read_csv, sudachi_read_csv, unidic_read_csv, utdict_read_csv};
@@ -1,746 +1,620 @@
-use std::io::{Result as ioResult, stdout, BufWriter, Write};
+use std::collections::{HashMap, HashSet};
+use std::io::{stdout, BufWriter};
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
+use std::process::ExitCode;
 
-use regex::Regex;
-use lazy_regex::regex_replace_all;
+use argh::FromArgs;
 
-use csv::{ReaderBuilder, Error as CsvError};
+mod config;
+use config::Config;
 
-use kanaria::string::{UCSStr, ConvertType};
-use kanaria::utils::ConvertTarget;
+use dict_to_mozc::{Error, DEFAULT_COST_FLOOR_MARGIN, CollisionPolicy, CostCurve, CostProfileSpec, CostProfiles, CostReference, Baseline, BaselineMatch, RejectReason, read_dictionary, PosResolver, DictionaryData, DictFormat, ExcludeList, InputEncoding, InputSpec, PriorityOrder, ReadOptions, Rejects, SortOrder, WordPolicy, pos_classes, Compression, OutputFile, MAX_USER_DICT_ENTRIES};
+use dict_to_mozc::cost::SAMPLE_COSTS;
+use dict_to_mozc::user_db::{GENERATED_PREFIX, build_storage, install_storage, verify_storage, write_storage};
 
-use crate::utils::convert_to_hiragana;
-use crate::utils::unicode_escape_to_char;
-use crate::utils::adjust_cost;
+#[derive(FromArgs)]
+/// Dictionary to Mozc Dictionary Formats: a tool for processing dictionary files
+struct Args {
+    /// path to the dictionary CSV file
+    #[argh(option, short = 'f')]
+    csv_file: Option<PathBuf>,
 
-mod utils {
-    use super::*;
+    /// path to the Mozc id.def file
+    #[argh(option, short = 'i')]
+    id_def: Option<PathBuf>,
 
-    // カタカナから読みを平仮名へ
-    pub fn convert_to_hiragana(text: &str) -> String {
-        let target: Vec<char> = text.chars().collect();
-        let mut yomi: String = UCSStr::convert(&target, ConvertType::Hiragana, ConvertTarget::ALL).iter().collect();
-        yomi = yomi.replace("ゐ", "い").replace("ゑ", "え");
-        yomi
-    }
+    /// generate Mozc User Dictionary formats
+    #[argh(switch, short = 'U')]
+    user_dict: bool,
 
-    // Unicode Escapeの記述が含まれる場合、それを変換する。
-    pub fn unicode_escape_to_char(text: &str) -> String {
-        regex_replace_all!(r#"\\u([0-9a-fA-F]{4})"#, text, |_, num: &str| {
-            let num: u32 = u32::from_str_radix(num, 16).unwrap();
-            std::char::from_u32(num).unwrap().to_string()
-        }).to_string()
-    }
+    /// generate Mozc system dictionary formats even if a config file sets user-dict
+    #[argh(switch)]
+    no_user_dict: bool,
 
-    // コスト計算
-    pub fn adjust_cost(cost: i32) -> i32 {
-        if cost < MIN_COST {
-            8000
-        } else if cost > MAX_COST {
-            MAX_COST
-        } else {
-            DEFAULT_COST + (cost / COST_ADJUSTMENT)
-        }
-    }
-}
+    /// target SudachiDict
+    #[argh(switch, short = 's')]
+    sudachi: bool,
 
-// 結果構造体
-// yomi,surface,hinshi_idの組み合わせで重複チェックされる。
-#[derive(Hash, Eq, PartialEq, Clone)]
-struct DictionaryKey {
-    yomi: String,
-    surface: String,
-    hinshi_id: i32,
-}
+    /// target NEologd dictionary
+    #[argh(switch, short = 'n')]
+    neologd: bool,
 
-// コストと品詞判定で判明した品詞の文字列
-struct DictionaryEntry {
-    key: DictionaryKey,
-    cost: i32,
-    pos: String,
-}
+    /// target UT dictionary
+    #[argh(switch, short = 'u')]
+    utdict: bool,
 
-// システム辞書型式とユーザー辞書型式
-struct DictionaryData {
-    entries: HashMap<DictionaryKey, DictionaryEntry>,
-    user_entries: HashMap<DictionaryKey, DictionaryEntry>,
-}
+    /// format of the -f file (sudachi, utdict, neologd, ipadic, unidic, byhand, mozc-user); instead of -s/-u/-n
+    #[argh(option)]
+    format: Option<DictFormat>,
 
-impl DictionaryData {
-    fn new() -> Self {
-        Self {
-            entries: HashMap::new(),
-            user_entries: HashMap::new(),
-        }
-    }
+    /// input file encoding: euc-jp, shift_jis, utf-8 (default: euc-jp for ipadic, utf-8 otherwise)
+    #[argh(option)]
+    encoding: Option<InputEncoding>,
 
-    fn add(&mut self, entry: DictionaryEntry, is_user_dict: bool) {
-        let target = if is_user_dict { &mut self.user_entries } else { &mut self.entries };
-        target.insert(entry.key.clone(), entry);
-    }
+    /// include place names (chimei)
+    #[argh(switch, short = 'P')]
+    places: bool,
 
-    fn output(&self, user_dict: bool) -> ioResult<()> {
-        let mut writer = BufWriter::new(stdout());
+    /// drop place names even if a config file sets places
+    #[argh(switch)]
+    no_places: bool,
 
-        // システム辞書のエントリーを出力
-        if ! user_dict {
-            for entry in self.entries.values() {
-                writeln!(
-                    writer,
-                    "{}\t{}\t{}\t{}\t{}",
-                    entry.key.yomi, entry.key.hinshi_id, entry.key.hinshi_id, entry.cost, entry.key.surface
-                )?;
-            }
-        } else {
-            // -Uオプションが設定されている場合のみユーザー辞書を出力
-            for entry in self.user_entries.values() {
-                if !self.entries.contains_key(&entry.key) {
-                    writeln!(
-                        writer,
-                        "{}\t{}\t{}\t{}",
-                        entry.key.yomi, entry.key.surface, entry.pos, "".to_string()
-                    )?;
-                }
-            }
-        }
+    /// include symbols (kigou)
+    #[argh(switch, short = 'S')]
+    symbols: bool,
 
-        writer.flush()
-    }
-}
-// Mozc ソースに含まれるsrc/data/dictionary_oss/id.def
-// 更新される可能性がある。
-type IdDef = HashMap<String, i32>;
-
-const DEFAULT_COST: i32 = 6000;
-const MIN_COST: i32 = 0;
-const MAX_COST: i32 = 10000;
-const COST_ADJUSTMENT: i32 = 10;
-
-// 辞書データの品詞情報とid.defを比較して品詞のidを確定する。
-fn id_expr(clsexpr: &str, id_def: &mut HashMap<String, i32>, class_map: &mut HashMap<String, i32>, default_noun_id: i32) -> i32 {
-    if let Some(&r) = id_def.get(clsexpr) {
-        class_map.insert(clsexpr.to_string(), r);
-        return r;
-    }
-
-    let expr: Vec<&str> = clsexpr.split(',').collect();
-    let mut best_match = (0, -1); // (マッチ数, ID)
-
-    for (key, &id) in id_def.iter() {
-        let key_parts: Vec<&str> = key.split(',').collect();
-
-        // 品詞の主要部分(最初の2-3項目)が一致するかを確認
-        if expr.len() >= 2 && key_parts.len() >= 2 &&
-            expr[0] == key_parts[0] && expr[1] == key_parts[1] {
-
-                let mut match_count = 2; // 最初の2項目は既に一致している
-                let mut is_valid_match = true;
-
-                // 残りの項目をチェック
-                for (i, (a, b)) in expr.iter().zip(key_parts.iter()).skip(2).enumerate() {
-                    if *b != "*" && *a == *b {
-                        match_count += 1;
-                    } else if i < 1 { // 3番目の項目（小分類）まで厳密にチェック
-                        is_valid_match = false;
-                        break;
-                    } else {
-                        // 4番目以降の項目は部分一致も許容
-                        if a.contains(b) || b.contains(a) {
-                            match_count += 1;
-                        }
-                        break; // 最初の不一致で終了
-                    }
-                }
+    /// drop symbols even if a config file sets symbols
+    #[argh(switch)]
+    no_symbols: bool,
 
-                // 特殊なケースの処理
-                if expr[0] == "名詞" && expr[1] == "固有名詞" {
-                    if match_count < 3 { // 固有名詞の場合、より詳細なマッチングを要求
-                        is_valid_match = false;
-                    }
-                } else if expr[0] == "動詞" {
-                    // 動詞の活用型のチェック
-                    let verb_type = expr.get(4).unwrap_or(&"");
-                    if verb_type.contains("五段") && key_parts.iter().any(|&k| k.contains("五段")) {
-                        match_count += 1;
-                    } else if verb_type.contains("四段") && key_parts.iter().any(|&k| k.contains("四段")) {
-                        match_count += 1;
-                    } else if verb_type.contains("一段") && key_parts.iter().any(|&k| k.contains("一段")) {
-                        match_count += 1;
-                    } else if verb_type.contains("カ変") && key_parts.iter().any(|&k| k.contains("カ変")) {
-                        match_count += 1;
-                    } else if verb_type.contains("サ変") && key_parts.iter().any(|&k| k.contains("サ変")) {
-                        match_count += 1;
-                    } else if verb_type.contains("ラ変") && key_parts.iter().any(|&k| k.contains("ラ変")) {
-                        match_count += 1;
-                    }
-                }
+    /// include words written only in ASCII, symbols and punctuation (English)
+    #[argh(switch)]
+    english: bool,
 
-                if is_valid_match && match_count > best_match.0 {
-                    best_match = (match_count, id);
-                }
-            }
-    }
+    /// drop English words even if a config file sets english
+    #[argh(switch)]
+    no_english: bool,
 
-    let result_id = if best_match.1 == -1 { default_noun_id } else { best_match.1 };
-    id_def.insert(clsexpr.to_string(), result_id);
-    class_map.insert(clsexpr.to_string(), result_id);
-    result_id
-}
+    /// keep English words that are proper nouns even without --english (default)
+    #[argh(switch)]
+    english_proper: bool,
 
-// id.defは更新されうるので、毎回、最新のものを読み込む。
-// 品詞判定が出来なかった場合、普通名詞とみなす。
-// default_noun_idは、その普通名詞のIDを格納しておく。
-fn read_id_def(path: &Path) -> Result<(IdDef, i32), CsvError> {
-    let mut hash = IdDef::new();
-    let mut reader = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b' ')
-        .from_path(path)?;
-    let mut default_noun_id: i32 = -1;
-
-    for result in reader.records() {
-        let record = result?;
-        let id: i32 = record[0].parse().unwrap();
-        let mut expr = record[1].replace("名詞,一般", "名詞,普通名詞")
-            .replace("名詞,数,", "名詞,数詞,")
-            .replace("形-","形,")
-            .replace("地域,","地名,");
-
-        // 名詞、一般名詞のIDを保存
-        if expr == "名詞,普通名詞,*,*,*,*,*" || expr == "名詞,一般,*,*,*,*,*" {
-            default_noun_id = id;
-        }
+    /// drop English words that are proper nouns too, unless --english
+    #[argh(switch)]
+    no_english_proper: bool,
 
-        let mut re = Regex::new(r"五段・カ行[^,]*").unwrap();
-        expr = re.replace(&expr, "五段・カ行").to_string();
+    /// include words written only in full-width forms, half-width kana, symbols and punctuation (default)
+    #[argh(switch)]
+    fullwidth_english: bool,
 
-        re = Regex::new(r"サ変([^,]*)").unwrap();
-        let cap = match re.captures(&expr) {
-            Some(i) => i.get(1).unwrap().as_str(),
-            None => "",
-        };
-        if cap != "" {
-            let mut s1 = String::from("サ変,");
-            s1.push_str(cap);
-            expr = re.replace(&expr, s1).to_string();
-        };
+    /// drop words written only in full-width forms and half-width kana, except proper nouns (see --no-fullwidth-english-proper)
+    #[argh(switch)]
+    no_fullwidth_english: bool,
 
-        re = Regex::new(r"ラ行([^,]*)").unwrap();
-        let cap = match re.captures(&expr) {
-            Some(i) => i.get(1).unwrap().as_str(),
-            None => "",
-        };
-        if cap != "" {
-            let mut s1 = String::from("ラ行,");
-            s1.push_str(cap);
-            expr = re.replace(&expr, s1).to_string();
-        };
+    /// keep full-width English words that are proper nouns even with --no-fullwidth-english (default)
+    #[argh(switch)]
+    fullwidth_english_proper: bool,
 
-        re = Regex::new(r"ワ行([^,]*)").unwrap();
-        let cap = match re.captures(&expr) {
-            Some(i) => i.get(1).unwrap().as_str(),
-            None => "",
-        };
-        if cap != "" {
-            let mut s1 = String::from("ワ行,");
-            s1.push_str(cap);
-            expr = re.replace(&expr, s1).to_string();
-        };
+    /// with --no-fullwidth-english, drop full-width English proper nouns too
+    #[argh(switch)]
+    no_fullwidth_english_proper: bool,
 
-        hash.insert(expr, id);
-    }
-    Ok((hash, default_noun_id))
-}
+    /// drop proper nouns
+    #[argh(switch)]
+    no_proper: bool,
 
-// ユーザー辞書の品詞と、id.defの品詞のマッピングを作成する
-struct PosMapping {
-    user_to_id_def: HashMap<String, Vec<String>>,
-    id_def_to_user: HashMap<String, String>,
-    id_to_user_pos_cache: HashMap<i32, String>,
-}
+    /// keep proper nouns even if a config file sets no-proper
+    #[argh(switch)]
+    proper: bool,
 
-impl PosMapping {
-    fn new() -> Self {
-        Self {
-            user_to_id_def: HashMap::new(),
-            id_def_to_user: HashMap::new(),
-            id_to_user_pos_cache: HashMap::new(),
-        }
-    }
+    /// input dictionary as FORMAT:PATH (sudachi, utdict, neologd, ipadic, unidic, byhand, mozc-user); repeatable
+    #[argh(option)]
+    input: Vec<InputSpec>,
 
-    fn add_mapping(&mut self, user_pos: &str, id_def_pos: &str) {
-        self.user_to_id_def.entry(user_pos.to_string())
-            .or_insert_with(Vec::new)
-            .push(id_def_pos.to_string());
-        self.id_def_to_user.insert(id_def_pos.to_string(), user_pos.to_string());
-    }
-}
+    /// comma separated format priority used when inputs collide (e.g. sudachi,neologd,utdict); defaults to the order of inputs
+    #[argh(option)]
+    priority: Option<PriorityOrder>,
 
-// マッピング作成
-fn create_pos_mapping() -> PosMapping {
-    let mut mapping = PosMapping::new();
-
-    // ユーザー辞書の品詞とid.defの品詞のマッピングを追加
-    mapping.add_mapping("固有名詞", "名詞,固有名詞,一般,*,*,*,*");
-    mapping.add_mapping("組織", "名詞,固有名詞,組織,*,*,*,*");
-    mapping.add_mapping("地名", "名詞,固有名詞,地名,*,*,*,*");
-    mapping.add_mapping("地名", "名詞,固有名詞,国,*,*,*,*");
-    mapping.add_mapping("地名", "名詞,接尾,地域,*,*,*,*");
-    mapping.add_mapping("名", "名詞,固有名詞,人名,名,*,*,*");
-    mapping.add_mapping("姓", "名詞,固有名詞,人名,姓,*,*,*");
-    mapping.add_mapping("人名", "名詞,固有名詞,人名,*,*,*,*");
-    mapping.add_mapping("接尾人名", "接尾辞,人名,*,*,*,*,*");
-    mapping.add_mapping("接尾地名", "接尾辞,地名,*,*,*,*,*");
-    mapping.add_mapping("動詞カ行五段", "動詞,一般,*,*,五段・カ行,*,*");
-    mapping.add_mapping("動詞カ変", "動詞,一般,*,*,カ変,*,*");
-    mapping.add_mapping("動詞サ行五段", "動詞,一般,*,*,五段・サ行,*,*");
-    mapping.add_mapping("動詞ハ行四", "動詞,非自立,*,*,四段・ハ行,*,*");
-    mapping.add_mapping("動詞マ行五段", "動詞,一般,*,*,五段・マ行,*,*");
-    mapping.add_mapping("動詞ラ行五段", "動詞,一般,*,*,五段・ラ行,*,*");
-    mapping.add_mapping("動詞ワ行五段", "動詞,自立,*,*,五段・ワ行,*,*");
-    mapping.add_mapping("動詞一段", "動詞,一般,*,*,一段,*,*");
-    mapping.add_mapping("動詞サ変", "動詞,一般,*,*,サ変,*,*");
-    mapping.add_mapping("動詞ラ変", "動詞,自立,*,*,ラ変,*,*");
-    mapping.add_mapping("動詞五段", "動詞,一般,*,*,五段,*,*");
-    mapping.add_mapping("名詞サ変", "名詞,普通名詞,サ変可能,*,*,*,*");
-
-    mapping.add_mapping("形容詞", "形容詞,一般,*,*,形容詞,*,*");
-    mapping.add_mapping("フィラー", "感動詞,フィラー,*,*,*,*,*");
-    mapping.add_mapping("BOS/EOS", "BOS/EOS,*,*,*,*,*,*");
-    mapping.add_mapping("その他", "その他,*,*,*,*,*,*");
-    mapping.add_mapping("感動詞", "感動詞,*,*,*,*,*,*");
-    mapping.add_mapping("助詞", "助詞,*,*,*,*,*,*");
-    mapping.add_mapping("助動詞", "助動詞,*,*,*,*,*,*");
-    mapping.add_mapping("終助詞", "助詞,終助詞,*,*,*,*,*");
-    mapping.add_mapping("名詞", "名詞,普通名詞,*,*,*,*,*");
-    mapping.add_mapping("固有名詞", "名詞,固有名詞,*,*,*,*,*");
-    mapping.add_mapping("数", "名詞,数詞,*,*,*,*,*");
-    mapping.add_mapping("助数詞", "名詞,数詞,*,*,*,*,*");
-    mapping.add_mapping("接尾一般", "接尾辞,*,*,*,*,*,*");
-    mapping.add_mapping("接続詞", "接続詞,*,*,*,*,*,*");
-    mapping.add_mapping("接頭語", "接頭辞,*,*,*,*,*,*");
-    mapping.add_mapping("副詞", "副詞,*,*,*,*,*,*");
-    mapping.add_mapping("形容詞", "形容詞,*,*,*,*,*,*");
-    mapping.add_mapping("記号", "補助記号,*,*,*,*,*,*");
-    mapping.add_mapping("名詞形動", "形状詞,*,*,*,*,*,*");
-    mapping.add_mapping("連体詞", "連体詞,*,*,*,*,*,*");
-    mapping.add_mapping("動詞", "動詞,*,*,*,*,*,*");
-    mapping.add_mapping("記号", "記号,*,*,*,*,*,*");
-
-    mapping
-}
+    /// fail on the first bad line instead of skipping and counting bad lines
+    #[argh(switch)]
+    strict: bool,
 
-// hinshi_idからユーザー辞書の品詞の判定
-fn get_user_pos_by_id(mapping: &mut PosMapping, id_def: &IdDef, hinshi_id: i32) -> Option<String> {
-    // キャッシュをチェック
-    if let Some(cached_pos) = mapping.id_to_user_pos_cache.get(&hinshi_id) {
-        return Some(cached_pos.clone());
-    }
-    let result = id_def.iter()
-        .find(|(_, &id)| id == hinshi_id)
-        .and_then(|(pos, _)| {
-            let parts: Vec<&str> = pos.split(',').collect();
-            let mut best_match: Option<(usize, &String)> = None;
-
-            for (key, value) in &mapping.id_def_to_user {
-                let key_parts: Vec<&str> = key.split(',').collect();
-                let mut match_count = 0;
-                let mut is_valid_match = true;
-
-                // 特殊なケース（記号など）の処理
-                if parts[0] == "記号" || parts[0] == "補助記号" {
-                    if key_parts[0] == "記号" {
-                        return Some(value.clone());
-                    }
-                    continue;
-                }
+    /// skip bad lines even if a config file sets strict
+    #[argh(switch)]
+    no_strict: bool,
 
-                // 全項目のマッチングを試みる
-                for (i, (a, b)) in parts.iter().zip(key_parts.iter()).enumerate() {
-                    if *b != "*" && *a == *b {
-                        match_count += 1;
-                    } else if i < 2 { // 最初の2項目（品詞大分類、中分類）は必ずマッチする必要がある
-                        is_valid_match = false;
-                        break;
-                    } else {
-                        // 後半の項目（活用型など）が一致しない場合
-                        // 完全一致でなくても、部分的な一致を許容する
-                        if a.contains(b) || b.contains(a) {
-                            match_count += 1;
-                        }
-                    }
-                }
+    /// write every dropped record with its reason code to this TSV file
+    #[argh(option)]
+    rejects: Option<PathBuf>,
 
-                // 固有名詞の場合、より詳細なマッチングを要求
-                if parts[0] == "名詞" && parts[1] == "固有名詞" && match_count < 4 {
-                    is_valid_match = false;
-                }
+    /// drop words matching `yomi surface` glob pairs in this file (default: $XDG_CONFIG_HOME/mozcdict-ext/exclude.txt if it exists)
+    #[argh(option)]
+    exclude: Option<PathBuf>,
 
-                // 動詞の活用型のマッチング
-                if parts[0] == "動詞" {
-                    let verb_type = parts.get(4).unwrap_or(&"");
-                    if verb_type.contains("五段") && key_parts.iter().any(|&k| k.contains("五段")) {
-                        match_count += 1;
-                    } else if verb_type.contains("四段") && key_parts.iter().any(|&k| k.contains("四段")) {
-                        match_count += 1;
-                    } else if verb_type.contains("一段") && key_parts.iter().any(|&k| k.contains("一段")) {
-                        match_count += 1;
-                    } else if verb_type.contains("カ変") && key_parts.iter().any(|&k| k.contains("カ変")) {
-                        match_count += 1;
-                    } else if verb_type.contains("サ変") && key_parts.iter().any(|&k| k.contains("サ変")) {
-                        match_count += 1;
-                    } else if verb_type.contains("ラ変") && key_parts.iter().any(|&k| k.contains("ラ変")) {
-                        match_count += 1;
-                    }
-                }
+    /// load the user dictionary POS to id.def mapping from this YAML file instead of the built-in table
+    #[argh(option)]
+    pos_mapping: Option<PathBuf>,
 
-                if is_valid_match && (best_match.is_none() || match_count > best_match.unwrap().0) {
-                    best_match = Some((match_count, value));
-                }
-            }
+    /// exact source POS to id.def overrides (YAML, e.g. clsmap.yaml) applied before heuristic matching
+    #[argh(option)]
+    pos_map: Option<PathBuf>,
 
-            best_match.map(|(_, v)| v.clone())
-        });
-    // 結果をキャッシュに保存
-    if let Some(ref pos) = result {
-        mapping.id_to_user_pos_cache.insert(hinshi_id, pos.clone());
-    }
+    /// write how each source POS tuple was mapped to id.def to this TSV file
+    #[argh(option)]
+    explain_pos: Option<PathBuf>,
+
+    /// cost conversion for a format as FORMAT:SETTINGS, e.g. sudachi:base=5000,scale=0.05,curve=log (settings: base, scale, curve=linear|log|quantile, negative, raw-max, min, max); repeatable
+    #[argh(option)]
+    cost_profile: Vec<CostProfileSpec>,
+
+    /// print how sample raw costs map to Mozc costs for each input format and exit
+    #[argh(switch)]
+    explain_cost: bool,
+
+    /// drop entries already in Mozc's dictionary_oss directory (dictionary*.txt)
+    #[argh(option)]
+    baseline: Option<PathBuf>,
+
+    /// how --baseline entries match: surface (same yomi and surface, default) or id (also same POS id)
+    #[argh(option)]
+    baseline_match: Option<BaselineMatch>,
 
-    result
+    /// take costs from Mozc's dictionary_oss directory: the same surface, else the median of the same POS id
+    #[argh(option)]
+    cost_reference: Option<PathBuf>,
+
+    /// keep entries whose reading is in Mozc's dictionary_oss directory at least --cost-floor-margin above its best cost
+    #[argh(option)]
+    cost_floor: Option<PathBuf>,
+
+    /// margin added to the best dictionary_oss cost of a reading for --cost-floor (default: 100)
+    #[argh(option)]
+    cost_floor_margin: Option<i32>,
+
+    /// which entry to keep when the same yomi, surface and POS id collide: priority (default), first, min-cost, max-cost
+    #[argh(option)]
+    collision: Option<CollisionPolicy>,
+
+    /// output sort order: yomi (default), id, cost, surface, mozc
+    #[argh(option)]
+    sort: Option<SortOrder>,
+
+    /// write the dictionary to this file instead of stdout; .xz, .gz and .zst are compressed
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+
+    /// compression for -o and --output-dir files: xz, gz, zst, none (default: by -o extension, none for --output-dir)
+    #[argh(option)]
+    compress: Option<Compression>,
+
+    /// write the user dictionary (-U) as a Mozc user_dictionary.db with one dictionary per input
+    #[argh(option)]
+    user_db: Option<PathBuf>,
+
+    /// merge the generated dictionaries into an existing user_dictionary.db (--user-db, default: ~/.config/mozc/user_dictionary.db), replacing all "mozcdict-ext" dictionaries, keeping your own dictionaries, the original file as .bak and the file before this run as .prev
+    #[argh(switch)]
+    install: bool,
+
+    /// turn off --install set by a config file
+    #[argh(switch)]
+    no_install: bool,
+
+    /// write the user dictionary (-U) to user_dic-NN.txt files in this directory instead of stdout
+    #[argh(option)]
+    output_dir: Option<PathBuf>,
+
+    /// maximum entries per user_dic-NN.txt file with --output-dir (default: 1000000, the Mozc import limit)
+    #[argh(option)]
+    max_entries: Option<usize>,
+
+    /// project config file read after ~/.config/mozcdict-ext/config.toml (default: ./mozcdict-ext.toml)
+    #[argh(option)]
+    config: Option<PathBuf>,
+
+    /// print the effective configuration as TOML and exit
+    #[argh(switch)]
+    print_config: bool,
 }
 
-// id.defからキーを検索
-fn search_key(def: &HashMap::<String, i32>, search: i32) -> String {
-    for (key, value) in def {
-        if value == &search {
-            return key.to_string();
-        } else {
-            continue;
-        }
+impl Args {
+    // コマンドラインで指定された項目を、設定の最後の層とする
+    // スイッチは、指定された場合のみ有効にする。--no-…のスイッチは、設定ファイルなどで有効にした項目を無効にする。
+    fn to_config(&self) -> Result<Config, String> {
+        let switch = |on: bool| on.then_some(true);
+        let toggle = |name: &str, on: bool, off: bool| match (on, off) {
+            (true, true) => Err(format!("--{0} and --no-{0} cannot be used together", name)),
+            (true, false) => Ok(Some(true)),
+            (false, true) => Ok(Some(false)),
+            (false, false) => Ok(None),
+        };
+        Ok(Config {
+            csv_file: self.csv_file.clone(),
+            id_def: self.id_def.clone(),
+            user_dict: toggle("user-dict", self.user_dict, self.no_user_dict)?,
+            sudachi: switch(self.sudachi),
+            neologd: switch(self.neologd),
+            utdict: switch(self.utdict),
+            format: self.format,
+            encoding: self.encoding,
+            places: toggle("places", self.places, self.no_places)?,
+            symbols: toggle("symbols", self.symbols, self.no_symbols)?,
+            english: toggle("english", self.english, self.no_english)?,
+            english_proper: toggle("english-proper", self.english_proper, self.no_english_proper)?,
+            fullwidth_english: toggle("fullwidth-english", self.fullwidth_english, self.no_fullwidth_english)?,
+            fullwidth_english_proper: toggle("fullwidth-english-proper", self.fullwidth_english_proper, self.no_fullwidth_english_proper)?,
+            no_proper: toggle("proper", self.proper, self.no_proper)?.map(|keep| !keep),
+            input: (!self.input.is_empty()).then(|| self.input.clone()),
+            priority: self.priority.clone(),
+            strict: toggle("strict", self.strict, self.no_strict)?,
+            rejects: self.rejects.clone(),
+            exclude: self.exclude.clone(),
+            pos_mapping: self.pos_mapping.clone(),
+            pos_map: self.pos_map.clone(),
+            explain_pos: self.explain_pos.clone(),
+            cost_profile: (!self.cost_profile.is_empty()).then(|| self.cost_profile.clone()),
+            baseline: self.baseline.clone(),
+            baseline_match: self.baseline_match,
+            cost_reference: self.cost_reference.clone(),
+            cost_floor: self.cost_floor.clone(),
+            cost_floor_margin: self.cost_floor_margin,
+            collision: self.collision,
+            sort: self.sort,
+            output: self.output.clone(),
+            compress: self.compress,
+            user_db: self.user_db.clone(),
+            install: toggle("install", self.install, self.no_install)?,
+            output_dir: self.output_dir.clone(),
+            max_entries: self.max_entries,
+        })
     }
-    return "".to_string();
 }
 
-// ユーザー辞書から品詞idの検索
-fn u_search_key(mapping: &mut PosMapping, id_def: &mut IdDef, hinshi_id: i32) -> Option<String> {
-    get_user_pos_by_id(mapping, id_def, hinshi_id)
+// 既定値、利用者の設定ファイル、プロジェクトの設定ファイル、環境変数、コマンドラインの順に重ねる
+fn load_config(args: &Args) -> Result<Config, Error> {
+    let mut config = Config::builtin();
+    if let Some(path) = Config::user_path() {
+        config.merge(Config::load_if_exists(&path)?);
+    }
+    match &args.config {
+        Some(path) => config.merge(Config::load(path)?),
+        None => config.merge(Config::load_if_exists(Path::new(config::PROJECT_CONFIG))?),
+    }
+    config.merge(Config::from_env()?);
+    config.merge(args.to_config().map_err(usage_error)?);
+    Ok(config)
 }
 
-// SudachiDict読み込み
-fn sudachi_read_csv(path: &Path, id_def: &mut IdDef, dict_data: &mut DictionaryData, default_noun_id: i32, user_dict_flag: bool, chimei_flag: bool, symbol_flag: bool) -> Result<(), csv::Error> {
-    let mut class_map = HashMap::<String, i32>::new();
-    let mut mapping = create_pos_mapping();
-    let reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b","[0])
-        .from_path(path);
-    //let mut list = Vec::new();
-    let kana_check = Regex::new(r"^[ぁ-ゖァ-ヺ]+$").unwrap();
-    let eisuu_check = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
-    //let chimei_check = Regex::new(r"地名").unwrap();
-    let kigou_check = Regex::new(r"^[a-zA-Z ]+$").unwrap();
-    for result in reader?.records() {
-        match result {
-            Err(_err) => continue,
-            Ok(record) => {
-                let data = record;
-                let s3 = &data[5].replace("補助記号", "記号"); //.replace("空白","記号");
-                if ! symbol_flag && &data[11] == "キゴウ" && s3.contains("記号") { continue };
-                if ! symbol_flag && s3 == "空白" { continue };
-                if ! symbol_flag && kigou_check.is_match(&data[4]) && ! (&data[6] == "固有名詞") { continue };
-                if ! kana_check.is_match(&data[11]) { continue };
-                // 地名を含む場合、オプション指定がなければ、英数のみの地名だけ残し、それ以外は省く。
-                if data[7].contains("地名") {
-                    if ! eisuu_check.is_match(&data[0]) && ! chimei_flag { continue };
-                };
-                let mut _yomi: String = convert_to_hiragana(&data[11]);
-                let s1 = unicode_escape_to_char(&_yomi);
-                let s2 = unicode_escape_to_char(&data[4]);
-                let s4 = &data[6].replace("非自立可能","非自立"); //.replace(r"^数詞$", "数");
-                let s5 = &data[9].replace("下一段","一段").replace("一段-","一段,").replace("段-","段・");
-                let s6 = &data[10].replace("形-", "形,");
-                let d: String = format!("{},{},{},{},{},{}", s3, s4, &data[7], &data[8], s5, s6);
-                let hinshi = class_map.get(&d);
-                let hinshi_id;
-                if hinshi == None {
-                    hinshi_id = id_expr(&d, &mut *id_def, &mut class_map, default_noun_id);
-                } else {
-                    hinshi_id = *hinshi.unwrap();
-                }
-                let mut cost = data[3].parse::<i32>().unwrap();
-                cost = adjust_cost(cost);
-                if user_dict_flag {
-                    match u_search_key(&mut mapping, id_def, hinshi_id) {
-                        Some(hinshi) => {
-                            dict_data.add(DictionaryEntry {
-                                key: DictionaryKey {
-                                    yomi: s1.to_string(),
-                                    surface: s2.to_string(),
-                                    hinshi_id,
-                                },
-                                cost,
-                                pos: hinshi,
-                            }, true);
-                        },
-                        None => {
-                            dict_data.add(DictionaryEntry {
-                                key: DictionaryKey {
-                                    yomi: s1.to_string(),
-                                    surface: s2.to_string(),
-                                    hinshi_id,
-                                },
-                                cost,
-                                pos: hinshi_id.to_string(),
-                            }, true);
-                        }
-                    }
-                } else {
-                    dict_data.add(DictionaryEntry {
-                        key: DictionaryKey {
-                            yomi: s1.to_string(),
-                            surface: s2.to_string(),
-                            hinshi_id,
-                        },
-                        cost,
-                        pos: "".to_string(),
-                    }, false);
-                }
-            }
+// user_dictionary.dbの辞書名を、入力元の優先順位ごとに決める
+// `mozcdict-ext sudachi core_lex.csv`のように型式とファイル名を並べ、同じ名前になる場合は`(2)`などを付ける。
+fn dictionary_names(inputs: &[InputSpec], ranks: &[usize]) -> HashMap<usize, String> {
+    let mut names = HashMap::new();
+    let mut used = HashSet::new();
+    for (input, &priority) in inputs.iter().zip(ranks) {
+        let file_name = input.path.file_name().map(|f| f.to_string_lossy()).unwrap_or_default();
+        let base = format!("{}{} {}", GENERATED_PREFIX, input.format, file_name);
+        let mut name = base.clone();
+        let mut n = 2;
+        while !used.insert(name.clone()) {
+            name = format!("{} ({})", base, n);
+            n += 1;
         }
+        names.insert(priority, name);
     }
-    Ok(())
+    names
 }
 
-// UtDict読み込み
-fn utdict_read_csv(path: &Path, id_def: &mut IdDef, dict_data: &mut DictionaryData, user_dict_flag: bool, chimei_flag: bool, symbol_flag: bool) -> Result<(), csv::Error> {
-    let mut mapping = create_pos_mapping();
-    let reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b"\t"[0])
-        .from_path(path);
-    //let mut list = Vec::new();
-    let kana_check = Regex::new(r"[ぁ-ゖァ-ヺ]").unwrap();
-    let kigou_check = Regex::new(r"^[a-zA-Z ]+$").unwrap();
-    for result in reader?.records() {
-        match result {
-            Err(_err) => continue,
-            Ok(record) => {
-                let data = record;
-                if ! kana_check.is_match(&data[0]) { continue };
-                let hinshi_id = data[1].parse::<i32>().unwrap();
-                if ! symbol_flag && kigou_check.is_match(&data[0]) && ! search_key(&id_def, hinshi_id).contains("固有名詞") { continue };
-                if search_key(&id_def, hinshi_id).contains("地名") && ! chimei_flag { continue }
-                let mut _yomi: String = convert_to_hiragana(&data[0]);
-                let s1 = unicode_escape_to_char(&_yomi);
-                let s2 = unicode_escape_to_char(&data[4]);
-                let mut cost = data[3].parse::<i32>().unwrap();
-                cost = adjust_cost(cost);
-                //let class: String = format!("{},{},{},{},{},{},{},{},{}", s1, s2, s3, hinshi_id, &data[6], &data[7], &data[8], &data[9], s4);
-                if user_dict_flag {
-                    match u_search_key(&mut mapping, id_def, hinshi_id) {
-                        Some(hinshi) => {
-                            dict_data.add(DictionaryEntry {
-                                key: DictionaryKey {
-                                    yomi: s1.to_string(),
-                                    surface: s2.to_string(),
-                                    hinshi_id,
-                                },
-                                cost,
-                                pos: hinshi,
-                            }, true);
-                        },
-                        None => {
-                            dict_data.add(DictionaryEntry {
-                                key: DictionaryKey {
-                                    yomi: s1.to_string(),
-                                    surface: s2.to_string(),
-                                    hinshi_id,
-                                },
-                                cost,
-                                pos: hinshi_id.to_string(),
-                            }, true);
-                        }
-                    }
-                } else {
-                    dict_data.add(DictionaryEntry {
-                        key: DictionaryKey {
-                            yomi: s1.to_string(),
-                            surface: s2.to_string(),
-                            hinshi_id,
-                        },
-                        cost,
-                        pos: "".to_string(),
-                    }, false);
-                }
-            }
+// 入力元の型式ごとに、コストの例がどう変換されるかを表示する
+// 入力の指定がなければ、コストの列を持つ全ての型式について表示する。
+fn explain_cost(inputs: &[InputSpec], profiles: &CostProfiles) {
+    let mut formats: Vec<DictFormat> = Vec::new();
+    for input in inputs {
+        if !formats.contains(&input.format) {
+            formats.push(input.format);
         }
     }
-    Ok(())
-}
-
-// Neologd読み込み
-fn neologd_read_csv(path: &Path, id_def: &mut IdDef, dict_data: &mut DictionaryData, default_noun_id: i32, user_dict_flag: bool, chimei_flag: bool, symbol_flag: bool) -> Result<(), csv::Error> {
-    let mut mapping = create_pos_mapping();
-    let mut class_map = HashMap::<String, i32>::new();
-    let reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b","[0])
-        .from_path(path);
-    //let mut list = Vec::new();
-    let kana_check = Regex::new(r"[ぁ-ゖァ-ヺ]").unwrap();
-    //let chimei_check = Regex::new(r"地名").unwrap();
-    let kigou_check = Regex::new(r"^[a-zA-Z ]+$").unwrap();
-    for result in reader?.records() {
-        match result {
-            Err(_err) => continue,
-            Ok(record) => {
-                let data = record;
-                if &data[11] == "キゴウ" && data[10].contains("記号") { continue };
-                if &data[4] == "空白" { continue };
-                if ! symbol_flag && kigou_check.is_match(&data[0]) && ! (&data[5] == "固有名詞") { continue };
-                if ! kana_check.is_match(&data[11]) { continue };
-                if ! chimei_flag && data[6].contains("地域") { continue };
-                let mut _yomi: String = convert_to_hiragana(&data[11]);
-                let s1 = unicode_escape_to_char(&_yomi);
-                let s2 = unicode_escape_to_char(&data[0]);
-                let s3 = &data[4];//.replace("補助記号", "記号"); //.replace("空白","記号");
-                let s4 = if &data[4] == "名詞" && &data[5] == "一般" {
-                    "普通名詞"
-                } else if &data[4] == "名詞" && &data[5] == "固有名詞" {
-                    &data[5] // 固有名詞はそのまま保持
-                } else {
-                    &data[5]
-                };
-                let s5 = &data[9];//.replace("形-", "形,");
-                let d: String = format!("{},{},{},{},{},{}", s3, s4, &data[6], &data[7], &data[8], s5);
-                let hinshi = class_map.get(&d);
-                let hinshi_id;
-                if hinshi == None {
-                    hinshi_id = id_expr(&d, &mut *id_def, &mut class_map, default_noun_id);
-                } else {
-                    hinshi_id = *hinshi.unwrap();
-                }
-                let mut cost = data[3].parse::<i32>().unwrap();
-                cost = adjust_cost(cost);
-                if user_dict_flag {
-                    match u_search_key(&mut mapping, id_def, hinshi_id) {
-                        Some(hinshi) => {
-                            dict_data.add(DictionaryEntry {
-                                key: DictionaryKey {
-                                    yomi: s1.to_string(),
-                                    surface: s2.to_string(),
-                                    hinshi_id,
-                                },
-                                cost,
-                                pos: hinshi,
-                            }, true);
-                        },
-                        None => {
-                            dict_data.add(DictionaryEntry {
-                                key: DictionaryKey {
-                                    yomi: s1.to_string(),
-                                    surface: s2.to_string(),
-                                    hinshi_id,
-                                },
-                                cost,
-                                pos: hinshi_id.to_string(),
-                            }, true);
-                        }
-                    }
-                } else {
-                    dict_data.add(DictionaryEntry {
-                        key: DictionaryKey {
-                            yomi: s1.to_string(),
-                            surface: s2.to_string(),
-                            hinshi_id,
-                        },
-                        cost,
-                        pos: "".to_string(),
-                    }, false);
-                }
+    if formats.is_empty() {
+        formats = vec![DictFormat::Sudachi, DictFormat::UtDict, DictFormat::Neologd, DictFormat::Ipadic, DictFormat::Unidic];
+    }
+    println!("format\traw\tcost");
+    for format in formats {
+        let profile = profiles.get(format);
+        println!("# {}: {}", format, profile);
+        if profile.curve == CostCurve::Quantile {
+            // 元のコストの分布によるため、品詞の分類ごとの百分位(p50など)で示す
+            for percent in (0..=100).step_by(10) {
+                println!("{}\tp{}\t{}", format, percent, profile.quantile(percent as f64 / 100.0));
             }
+            continue;
+        }
+        for raw in SAMPLE_COSTS {
+            println!("{}\t{}\t{}", format, raw, profile.apply(raw));
         }
     }
-    Ok(())
 }
 
-use argh::FromArgs;
-
-#[derive(FromArgs)]
-/// Dictionary to Mozc Dictionary Formats: a tool for processing dictionary files
-struct Args {
-    /// path to the dictionary CSV file
-    #[argh(option, short = 'f')]
-    csv_file: Option<PathBuf>,
-
-    /// path to the Mozc id.def file
-    #[argh(option, short = 'i')]
-    id_def: Option<PathBuf>,
-
-    /// generate Mozc User Dictionary formats
-    #[argh(switch, short = 'U')]
-    user_dict: bool,
-
-    /// target SudachiDict
-    #[argh(switch, short = 's')]
-    sudachi: bool,
-
-    /// target NEologd dictionary
-    #[argh(switch, short = 'n')]
-    neologd: bool,
-
-    /// target UT dictionary
-    #[argh(switch, short = 'u')]
-    utdict: bool,
-
-    /// include place names (chimei)
-    #[argh(switch, short = 'P')]
-    places: bool,
-
-    /// include symbols (kigou)
-    #[argh(switch, short = 'S')]
-    symbols: bool,
+// コマンドラインの指定の誤り
+fn usage_error(message: impl Into<String>) -> Error {
+    Error::Config { path: PathBuf::from("(command line)"), message: message.into() }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> ExitCode {
     let args: Args = argh::from_env();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let current_dir = std::env::current_dir()?;
-    
-    // CSVファイルのパスを取得
-    let csv_path = args.csv_file.unwrap_or_else(|| current_dir.join("all.csv"));
+fn run(args: &Args) -> Result<(), Error> {
+    // 設定の読み込み
+    let config = load_config(args)?;
+    if args.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+    let flag = |on: Option<bool>| on.unwrap_or(false);
+
+    if config.output_dir.is_some() && !flag(config.user_dict) {
+        return Err(usage_error("--output-dir requires -U"));
+    }
+    if (config.user_db.is_some() || flag(config.install)) && !flag(config.user_dict) {
+        return Err(usage_error("--user-db and --install require -U"));
+    }
+    if [config.output.is_some(), config.output_dir.is_some(), config.user_db.is_some() || flag(config.install)].iter().filter(|&&on| on).count() > 1 {
+        return Err(usage_error("-o, --output-dir and --user-db/--install cannot be used together"));
+    }
+    if config.compress.is_some() && config.output_dir.is_none() && config.output.is_none() {
+        return Err(usage_error("--compress requires -o or --output-dir"));
+    }
+
+    let current_dir = std::env::current_dir().map_err(|e| Error::io(Path::new("."), e))?;
     
     // id.defファイルのパスを取得
-    let id_def_path = args.id_def.unwrap_or_else(|| current_dir.join("id.def"));
+    let id_def_path = config.id_def.unwrap_or_else(|| current_dir.join("id.def"));
+
+    // 入力ファイルの一覧
+    // -s/-u/-n(または--format)と-fの指定は、--inputの先頭に追加する。
+    let mut inputs = Vec::new();
+    let legacy_format = if config.format.is_some() {
+        config.format
+    } else if flag(config.sudachi) {
+        Some(DictFormat::Sudachi)
+    } else if flag(config.utdict) {
+        Some(DictFormat::UtDict)
+    } else if flag(config.neologd) {
+        Some(DictFormat::Neologd)
+    } else {
+        None
+    };
+    if let Some(format) = legacy_format {
+        let path = config.csv_file.unwrap_or_else(|| current_dir.join("all.csv"));
+        inputs.push(InputSpec { format, path });
+    }
+    inputs.extend(config.input.unwrap_or_default());
+
+    // 入力元の型式ごとのコストの変換方法
+    let cost_profiles = CostProfiles::from_specs(&config.cost_profile.clone().unwrap_or_default()).map_err(usage_error)?;
+    if args.explain_cost {
+        explain_cost(&inputs, &cost_profiles);
+        return Ok(());
+    }
 
     // ファイルの存在チェック
-    if !csv_path.exists() {
-        eprintln!("Error: CSV file not found at {:?}", csv_path);
-        return Err("CSV file not found".into());
+    for input in &inputs {
+        if !input.path.exists() {
+            return Err(Error::Config { path: input.path.clone(), message: "CSV file not found".to_string() });
+        }
     }
 
     if !id_def_path.exists() {
-        eprintln!("Error: id.def file not found at {:?}", id_def_path);
-        return Err("id.def file not found".into());
+        return Err(Error::Config { path: id_def_path, message: "id.def file not found".to_string() });
     }
 
     let mut dict_data = DictionaryData::new();
+    dict_data.set_collision_policy(config.collision.unwrap_or_default());
     
     // id.defの読み込み
-    let (mut id_def, default_noun_id) = read_id_def(&id_def_path)?;
+    let mut resolver = PosResolver::load(&id_def_path)?;
+
+    // ユーザー辞書の品詞の対応表
+    if let Some(path) = &config.pos_mapping {
+        resolver.load_mapping(path)?;
+    }
+
+    // 辞書データの品詞からid.defの品詞への対応表
+    if let Some(path) = &config.pos_map {
+        resolver.load_overrides(path)?;
+    }
+
+    // 品詞判定の詳細の記録
+    if config.explain_pos.is_some() {
+        resolver.enable_explain();
+    }
+
+    // 除外する単語の一覧
+    // 指定がなければ、既定の場所にあるものを用いる。
+    let exclude_path = config.exclude.clone()
+        .or_else(|| ExcludeList::default_path().filter(|path| path.exists()));
+    let exclude = match &exclude_path {
+        Some(path) => ExcludeList::load(path)?,
+        None => ExcludeList::new(),
+    };
+
+    // 除外された行の記録
+    let mut rejects = match &config.rejects {
+        Some(path) => Rejects::create(path)?,
+        None => Rejects::new(),
+    };
+
+    // 英語、全角英語、固有名詞の扱い
+    let default_policy = WordPolicy::default();
+    let policy = WordPolicy {
+        english: config.english.unwrap_or(default_policy.english),
+        english_proper: config.english_proper.unwrap_or(default_policy.english_proper),
+        fullwidth_english: config.fullwidth_english.unwrap_or(default_policy.fullwidth_english),
+        fullwidth_english_proper: config.fullwidth_english_proper.unwrap_or(default_policy.fullwidth_english_proper),
+        no_proper: config.no_proper.unwrap_or(default_policy.no_proper),
+    };
+
+    // 品詞の分類ごとのコストの分布を求めるための、品詞IDと分類の対応
+    let classes = pos_classes(&resolver.id_def);
 
     // 辞書の読み込み処理
-    if args.sudachi {
-        sudachi_read_csv(&csv_path, &mut id_def, &mut dict_data, default_noun_id, args.user_dict, args.places, args.symbols)?;
-    } else if args.utdict {
-        utdict_read_csv(&csv_path, &mut id_def, &mut dict_data, args.user_dict, args.places, args.symbols)?;
-    } else if args.neologd {
-        neologd_read_csv(&csv_path, &mut id_def, &mut dict_data, default_noun_id, args.user_dict, args.places, args.symbols)?;
+    let ranks = config.priority.unwrap_or_default().ranks(&inputs);
+    for (input, &priority) in inputs.iter().zip(&ranks) {
+        let opts = ReadOptions {
+            user_dict_flag: flag(config.user_dict),
+            chimei_flag: flag(config.places),
+            symbol_flag: flag(config.symbols),
+            priority,
+            strict: flag(config.strict),
+            encoding: config.encoding,
+            exclude: Some(&exclude),
+            policy,
+            cost: cost_profiles.get(input.format),
+        };
+        let stats = read_dictionary(input, &mut resolver, &mut dict_data, &opts, &mut rejects)?;
+        if stats.bad_lines > 0 {
+            eprintln!("{}: skipped {} bad lines of {}", input.path.display(), stats.bad_lines, stats.records);
+        }
+        // 入力元を読み終えてから、コストの分布に基づいてコストを決め直す
+        if opts.cost.curve == CostCurve::Quantile {
+            dict_data.quantile_costs(priority, &opts.cost, &classes);
+        }
+    }
+
+    // 重複したキーの件数を、残し方とともに表示
+    let collisions = dict_data.collisions();
+    if collisions.resolved > 0 {
+        eprintln!("collisions ({}): resolved {}, replaced {}", dict_data.collision_policy(), collisions.resolved, collisions.replaced);
+    }
+
+    // Mozcのシステム辞書に既にあるエントリーを取り除く
+    if let Some(dir) = &config.baseline {
+        let baseline = Baseline::load(dir)?;
+        let removed = dict_data.remove_baseline(&baseline, config.baseline_match.unwrap_or_default());
+        let mut counts = vec![0; inputs.len()];
+        for entry in &removed {
+            if let Some(count) = counts.get_mut(entry.priority) {
+                *count += 1;
+            }
+            rejects.entry(RejectReason::Baseline, entry)?;
+        }
+        for (input, &priority) in inputs.iter().zip(&ranks) {
+            eprintln!("baseline {}: removed {}", input.path.display(), counts[priority]);
+        }
+    }
+
+    // Mozcのシステム辞書のコストを元に、コストを決め直す
+    if let Some(dir) = &config.cost_reference {
+        let baseline = Baseline::load(dir)?;
+        let counts = dict_data.reference_costs(&CostReference::new(&baseline));
+        eprintln!("cost reference: surface {}, hinshi_id {}, fallback {}", counts.surface, counts.hinshi_id, counts.fallback);
+    }
+
+    // システム辞書にある読みでは、生成したエントリーがMozcの候補より上位に来ないようにする
+    if let Some(dir) = &config.cost_floor {
+        let baseline = Baseline::load(dir)?;
+        let margin = config.cost_floor_margin.unwrap_or(DEFAULT_COST_FLOOR_MARGIN);
+        let raised = dict_data.raise_cost_floor(&baseline, margin);
+        let mut counts = vec![0; inputs.len()];
+        for entry in &raised {
+            if let Some(count) = counts.get_mut(entry.priority) {
+                *count += 1;
+            }
+        }
+        for (input, &priority) in inputs.iter().zip(&ranks) {
+            eprintln!("cost floor {}: adjusted {}", input.path.display(), counts[priority]);
+        }
+    }
+
+    // 除外リストのパターンごとの件数を表示
+    if !exclude.is_empty() {
+        exclude.print_summary();
+    }
+
+    // --user-dbの指定があれば、入力元ごとの辞書としてuser_dictionary.dbへ出力する。
+    // Mozcの品詞(PosType)にない品詞名のエントリーは、除外された行として記録する。
+    let sort = config.sort.unwrap_or_default();
+    let user_db_path = match (&config.user_db, flag(config.install)) {
+        (Some(path), _) => Some(path.clone()),
+        (None, true) => dict_to_mozc::user_db::default_path(),
+        (None, false) => None,
+    };
+    let mut storage = None;
+    if let Some(path) = &user_db_path {
+        let max_entries = config.max_entries.unwrap_or(MAX_USER_DICT_ENTRIES);
+        let names = dictionary_names(&inputs, &ranks);
+        let (built, skipped) = build_storage(&dict_data, &names, max_entries, sort)
+            .map_err(|message| Error::Config { path: path.clone(), message })?;
+        for entry in &skipped {
+            rejects.entry(RejectReason::NoUserDbPos, entry)?;
+        }
+        if !skipped.is_empty() {
+            eprintln!("skipped {} entries without a Mozc user dictionary POS (see --rejects)", skipped.len());
+        }
+        storage = Some(built);
+    }
+
+    // 除外された件数を理由ごとに表示
+    if config.rejects.is_some() {
+        rejects.flush()?;
+        rejects.print_summary();
+    }
+
+    // 品詞判定の詳細の出力
+    if let (Some(path), Some(explain)) = (&config.explain_pos, resolver.explain()) {
+        explain.write(path)?;
     }
 
     // 辞書データの出力
-    dict_data.output(args.user_dict)?;
+    // --installの場合は、既存のファイルの利用者の辞書を残して、生成した辞書のみを置き換える。
+    if let (Some(path), Some(storage)) = (&user_db_path, &storage) {
+        let result = if flag(config.install) {
+            install_storage(path, storage).map(|summary| {
+                if let Some(backup) = &summary.backup {
+                    eprintln!("backed up {} to {}", path.display(), backup.display());
+                }
+                if let Some(previous) = &summary.previous {
+                    eprintln!("saved the previous {} as {}", path.display(), previous.display());
+                }
+                eprintln!("kept {} dictionaries, replaced {}", summary.kept, summary.replaced);
+            })
+        } else {
+            write_storage(path, storage)
+        };
+        // 書き出したファイルを読み直して確かめる
+        result.and_then(|_| verify_storage(path, storage))?;
+        for dict in &storage.dictionaries {
+            eprintln!("wrote {}: {}", dict.name(), dict.entries.len());
+        }
+        return Ok(());
+    }
+
+    // --output-dirの指定があれば、ユーザー辞書を分割してファイルへ出力する。
+    if let Some(dir) = &config.output_dir {
+        let max_entries = config.max_entries.unwrap_or(MAX_USER_DICT_ENTRIES);
+        let written = dict_data.output_chunks(dir, max_entries, sort, config.compress.unwrap_or_default())?;
+        for (path, count) in written {
+            eprintln!("wrote {}: {}", path.display(), count);
+        }
+        return Ok(());
+    }
+
+    // -oの指定があれば、ファイルへ(拡張子または--compressに応じて圧縮して)出力する。
+    if let Some(path) = &config.output {
+        let mut writer = OutputFile::create(path, config.compress)?;
+        dict_data.output(&mut writer, flag(config.user_dict), sort)
+            .and_then(|_| writer.finish())
+            .map_err(|e| Error::io(path, e))?;
+        return Ok(());
+    }
+    let mut writer = BufWriter::new(stdout());
+    dict_data.output(&mut writer, flag(config.user_dict), sort)
+        .map_err(|e| Error::io(Path::new("(stdout)"), e))?;
 
     Ok(())
 }
@@ -0,0 +1,137 @@
+//! 出力ファイルの作成と圧縮
+
+use std::fs::File;
+use std::io::{BufWriter, Result as ioResult, Write};
+use std::path::Path;
+
+use flate2::write::GzEncoder;
+use xz2::stream::{Check, Stream};
+use xz2::write::XzEncoder;
+
+use crate::error::Error;
+
+// xz -9 -eと同じ設定(LZMA_PRESET_EXTREME)
+const XZ_PRESET: u32 = 9 | 0x8000_0000;
+
+// zstdの圧縮レベル(zstd -19相当)
+const ZSTD_LEVEL: i32 = 19;
+
+/// 出力の圧縮形式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// 圧縮しない
+    #[default]
+    None,
+    /// xz(.xz)
+    Xz,
+    /// gzip(.gz)
+    Gzip,
+    /// Zstandard(.zst)
+    Zstd,
+}
+
+impl std::str::FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "xz" => Ok(Compression::Xz),
+            "gz" | "gzip" => Ok(Compression::Gzip),
+            "zst" | "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression: {} (xz, gz, zst, none)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Compression::None => "none",
+            Compression::Xz => "xz",
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+        })
+    }
+}
+
+impl Compression {
+    /// ファイルの拡張子から圧縮形式を判定する
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("xz") => Compression::Xz,
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// 圧縮したファイルに付ける拡張子(`.xz`など)
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Xz => ".xz",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// 圧縮しながら書き出すファイル
+/// 圧縮形式の末尾を書き出すため、最後に[`OutputFile::finish`]を呼ぶ。
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Xz(XzEncoder<BufWriter<File>>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputFile {
+    /// ファイルを作成する
+    /// 圧縮形式の指定がない場合は、pathの拡張子から判定する。
+    pub fn create(path: &Path, compression: Option<Compression>) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|e| Error::io(path, e))?;
+        let writer = BufWriter::new(file);
+        Ok(match compression.unwrap_or_else(|| Compression::from_path(path)) {
+            Compression::None => OutputFile::Plain(writer),
+            Compression::Xz => {
+                let stream = Stream::new_easy_encoder(XZ_PRESET, Check::Crc64)
+                    .map_err(|e| Error::io(path, e.into()))?;
+                OutputFile::Xz(XzEncoder::new_stream(writer, stream))
+            },
+            Compression::Gzip => OutputFile::Gzip(GzEncoder::new(writer, flate2::Compression::best())),
+            Compression::Zstd => OutputFile::Zstd(zstd::Encoder::new(writer, ZSTD_LEVEL).map_err(|e| Error::io(path, e))?),
+        })
+    }
+
+    /// 圧縮形式の末尾を書き出し、ファイルを閉じる
+    pub fn finish(self) -> ioResult<()> {
+        let mut writer = match self {
+            OutputFile::Plain(w) => w,
+            OutputFile::Xz(w) => w.finish()?,
+            OutputFile::Gzip(w) => w.finish()?,
+            OutputFile::Zstd(w) => w.finish()?,
+        };
+        writer.flush()
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> ioResult<usize> {
+        match self {
+            OutputFile::Plain(w) => w.write(buf),
+            OutputFile::Xz(w) => w.write(buf),
+            OutputFile::Gzip(w) => w.write(buf),
+            OutputFile::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> ioResult<()> {
+        match self {
+            OutputFile::Plain(w) => w.flush(),
+            OutputFile::Xz(w) => w.flush(),
+            OutputFile::Gzip(w) => w.flush(),
+            OutputFile::Zstd(w) => w.flush(),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! 品詞判定の詳細(--explain-pos)
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::Error;
+use crate::id_def::{IdDef, PosCandidate, id_candidates};
+use crate::pos_override::PosOverrides;
+
+// 次点として表示する候補の数
+const RUNNERS_UP: usize = 3;
+
+/// 品詞の判定方法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PosMatch {
+    /// id.defの品詞と完全に一致した
+    Exact,
+    /// 点数により判定した
+    Scored,
+    /// 候補がなく、普通名詞とみなした
+    Fallback,
+    /// 対応表(--pos-map)により判定した
+    Override,
+}
+
+impl PosMatch {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PosMatch::Exact => "exact",
+            PosMatch::Scored => "scored",
+            PosMatch::Fallback => "fallback",
+            PosMatch::Override => "override",
+        }
+    }
+}
+
+/// 辞書データの品詞の文字列ごとの判定結果
+#[derive(Clone, Debug)]
+pub struct PosExplanation {
+    pub id: i32,
+    /// 判定された候補の点数(完全一致、対応表による場合、普通名詞とみなした場合は0)
+    pub score: i32,
+    pub matched: PosMatch,
+    /// 次点の候補(IDごとに最も点数の高いもの)
+    pub runners_up: Vec<PosCandidate>,
+    /// この品詞で判定されたエントリーの数
+    pub count: usize,
+}
+
+/// 品詞判定の詳細の記録
+pub struct PosExplain {
+    id_lines: HashMap<i32, String>,
+    tuples: HashMap<String, PosExplanation>,
+    /// 対応表の品詞と、そのID
+    overrides: BTreeMap<String, i32>,
+}
+
+impl PosExplain {
+    /// 判定前のid.defから、IDごとの品詞を控えておく
+    pub fn new(id_def: &IdDef) -> Self {
+        let mut id_lines: HashMap<i32, String> = HashMap::new();
+        for (expr, &id) in id_def {
+            let line = id_lines.entry(id).or_insert_with(|| expr.clone());
+            if expr < line {
+                *line = expr.clone();
+            }
+        }
+        Self { id_lines, tuples: HashMap::new(), overrides: BTreeMap::new() }
+    }
+
+    /// 品詞の文字列の判定結果を記録する
+    /// id_defは、判定前の状態であること。
+    pub fn add(&mut self, clsexpr: &str, id_def: &IdDef, default_noun_id: i32) {
+        if self.tuples.contains_key(clsexpr) {
+            return;
+        }
+        let explanation = if let Some(&id) = id_def.get(clsexpr) {
+            PosExplanation { id, score: 0, matched: PosMatch::Exact, runners_up: Vec::new(), count: 0 }
+        } else {
+            let mut candidates = id_candidates(clsexpr, id_def).into_iter();
+            match candidates.next() {
+                Some(best) => {
+                    let mut runners_up: Vec<PosCandidate> = Vec::new();
+                    for c in candidates {
+                        if runners_up.len() == RUNNERS_UP {
+                            break;
+                        }
+                        if c.id != best.id && runners_up.iter().all(|r| r.id != c.id) {
+                            runners_up.push(c);
+                        }
+                    }
+                    PosExplanation { id: best.id, score: best.score, matched: PosMatch::Scored, runners_up, count: 0 }
+                },
+                None => PosExplanation { id: default_noun_id, score: 0, matched: PosMatch::Fallback, runners_up: Vec::new(), count: 0 },
+            }
+        };
+        self.tuples.insert(clsexpr.to_string(), explanation);
+    }
+
+    /// 対応表(--pos-map)を控えておく
+    /// 一度も使われなかった行を出力するために用いる。
+    pub fn set_overrides(&mut self, overrides: &PosOverrides) {
+        self.overrides = overrides.iter().map(|(source, rule)| (source.clone(), rule.id)).collect();
+    }
+
+    /// 対応表による判定結果を記録する
+    pub fn add_override(&mut self, clsexpr: &str, id: i32) {
+        self.tuples.entry(clsexpr.to_string())
+            .or_insert(PosExplanation { id, score: 0, matched: PosMatch::Override, runners_up: Vec::new(), count: 0 });
+    }
+
+    /// 対応表のうち、一度も使われなかった品詞とそのID
+    pub fn unused_overrides(&self) -> impl Iterator<Item = (&String, i32)> {
+        self.overrides.iter()
+            .filter(|(source, _)| !self.tuples.contains_key(*source))
+            .map(|(source, &id)| (source, id))
+    }
+
+    /// 品詞の文字列で判定されたエントリーを数える
+    pub fn count(&mut self, clsexpr: &str) {
+        if let Some(explanation) = self.tuples.get_mut(clsexpr) {
+            explanation.count += 1;
+        }
+    }
+
+    /// 品詞の文字列ごとの判定結果
+    pub fn get(&self, clsexpr: &str) -> Option<&PosExplanation> {
+        self.tuples.get(clsexpr)
+    }
+
+    /// IDに対応するid.defの品詞
+    pub fn id_line(&self, id: i32) -> &str {
+        self.id_lines.get(&id).map(|s| s.as_str()).unwrap_or("")
+    }
+
+    /// 判定結果をタブ区切りで書き出す
+    /// エントリー数の多い順に、エントリー数、品詞の文字列、判定方法、ID、id.defの品詞、点数、次点の候補を出力する。
+    /// 対応表のうち一度も使われなかった行は、最後にエントリー数0、判定方法unused_overrideとして出力する。
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut tuples: Vec<(&String, &PosExplanation)> = self.tuples.iter().collect();
+        tuples.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        writeln!(writer, "#count\ttuple\tmatch\tid\tid.def\tscore\trunners_up")?;
+        for (tuple, e) in tuples {
+            let runners_up: Vec<String> = e.runners_up.iter()
+                .map(|c| format!("{}:{}:{}", c.id, c.score, self.id_line(c.id)))
+                .collect();
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                e.count, tuple, e.matched.code(), e.id, self.id_line(e.id), e.score, runners_up.join(" | ")
+            )?;
+        }
+        for (source, id) in self.unused_overrides() {
+            writeln!(writer, "0\t{}\tunused_override\t{}\t{}\t0\t", source, id, self.id_line(id))?;
+        }
+        writer.flush()
+    }
+
+    /// 判定結果をpathへ書き出す
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let file = File::create(path).map_err(|e| Error::io(path, e))?;
+        self.write_to(&mut BufWriter::new(file)).map_err(|e| Error::io(path, e))
+    }
+}
@@ -0,0 +1,315 @@
+//! ユーザー辞書の品詞とid.defの品詞の対応付け
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::id_def::{IdDef, normalize_id_def_pos, verb_type_matches};
+
+/// ユーザー辞書の品詞と、id.defの品詞のマッピングを作成する
+pub struct PosMapping {
+    user_to_id_def: HashMap<String, Vec<String>>,
+    id_def_to_user: HashMap<String, String>,
+    id_to_user_pos_cache: HashMap<i32, String>,
+}
+
+impl Default for PosMapping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PosMapping {
+    pub fn new() -> Self {
+        Self {
+            user_to_id_def: HashMap::new(),
+            id_def_to_user: HashMap::new(),
+            id_to_user_pos_cache: HashMap::new(),
+        }
+    }
+
+    /// ユーザー辞書の品詞に、対応するid.defの品詞を追加する
+    pub fn add_mapping(&mut self, user_pos: &str, id_def_pos: &str) {
+        self.user_to_id_def.entry(user_pos.to_string())
+            .or_default()
+            .push(id_def_pos.to_string());
+        self.id_def_to_user.insert(id_def_pos.to_string(), user_pos.to_string());
+    }
+}
+
+// 既定の対応表
+const DEFAULT_POS_MAPPING: &str = include_str!("pos_mapping.yaml");
+
+/// Mozcのユーザー辞書の品詞名
+pub const MOZC_USER_POS: [&str; 44] = [
+    "名詞", "短縮よみ", "サジェストのみ", "固有名詞", "人名", "姓", "名", "組織", "地名",
+    "名詞サ変", "名詞形動", "数", "アルファベット", "記号", "顔文字",
+    "副詞", "連体詞", "接続詞", "感動詞", "接頭語", "助数詞", "接尾一般", "接尾人名", "接尾地名",
+    "動詞ワ行五段", "動詞カ行五段", "動詞サ行五段", "動詞タ行五段", "動詞ナ行五段",
+    "動詞マ行五段", "動詞ラ行五段", "動詞ガ行五段", "動詞バ行五段", "動詞ハ行四段",
+    "動詞一段", "動詞カ変", "動詞サ変", "動詞ザ変", "動詞ラ変",
+    "形容詞", "終助詞", "句読点", "独立語", "抑制単語",
+];
+
+impl PosMapping {
+    /// YAMLの対応表を読み込む
+    /// ユーザー辞書の品詞名をキーとし、id.defの品詞をひとつ、またはリストで記述する。
+    /// id.defの品詞は、id.defを読み込むときと同じく正規化するので、id.defの行をそのまま書いてもよい。
+    pub fn from_yaml(text: &str) -> Result<Self, String> {
+        let table: serde_yaml::Mapping = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+        let mut mapping = PosMapping::new();
+        for (user_pos, patterns) in &table {
+            let user_pos = user_pos.as_str()
+                .ok_or_else(|| format!("user POS name must be a string: {:?}", user_pos))?;
+            let patterns: Vec<&str> = match patterns {
+                serde_yaml::Value::String(p) => vec![p.as_str()],
+                serde_yaml::Value::Sequence(list) => list.iter()
+                    .map(|p| p.as_str().ok_or_else(|| format!("{}: id.def POS must be a string: {:?}", user_pos, p)))
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(format!("{}: expected an id.def POS or a list of them", user_pos)),
+            };
+            for pattern in patterns {
+                mapping.add_mapping(user_pos, &normalize_id_def_pos(pattern));
+            }
+        }
+        Ok(mapping)
+    }
+
+    /// 対応表のファイルを読み込み、id.defと照合する
+    /// Mozcのユーザー辞書にない品詞名や、どのid.defの品詞にも一致しない品詞があればエラーとする。
+    pub fn load(path: &Path, id_def: &IdDef) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let mapping = PosMapping::from_yaml(&text)
+            .map_err(|message| Error::Config { path: path.to_path_buf(), message })?;
+        let problems = mapping.validate(id_def);
+        if !problems.is_empty() {
+            return Err(Error::Config { path: path.to_path_buf(), message: problems.join("; ") });
+        }
+        Ok(mapping)
+    }
+
+    /// 対応表の問題点を返す
+    /// ユーザー辞書の品詞名は、Mozcのユーザー辞書の品詞(MOZC_USER_POS)のみとする。
+    /// id.defの品詞は、`*`以外の項目がすべてid.defのいずれかの行と一致しなければならない。
+    pub fn validate(&self, id_def: &IdDef) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut user_pos_list: Vec<&String> = self.user_to_id_def.keys().collect();
+        user_pos_list.sort();
+        for user_pos in user_pos_list {
+            if !MOZC_USER_POS.contains(&user_pos.as_str()) {
+                problems.push(format!("unknown user POS name: {}", user_pos));
+            }
+            for pattern in &self.user_to_id_def[user_pos] {
+                let key_parts: Vec<&str> = pattern.split(',').collect();
+                let matched = id_def.keys().any(|pos| {
+                    let parts: Vec<&str> = pos.split(',').collect();
+                    pattern_matches(&parts, &key_parts)
+                });
+                if !matched {
+                    problems.push(format!("{}: pattern matches no id.def line: {}", user_pos, pattern));
+                }
+            }
+        }
+        problems
+    }
+
+    /// ユーザー辞書の品詞名から、id.defのIDを逆引きする
+    /// 対応表に書かれた順にid.defの品詞と照合し、最初に一致した品詞のうち、
+    /// 活用形が基本形(または活用しない)で原形の指定のないものを優先し、その中でIDの小さいものを選ぶ。
+    /// どの品詞も完全には一致しない場合は、hinshi_idからの判定と同じ点数で照合し、点数の最も高いものを選ぶ。
+    pub fn user_pos_to_id(&self, user_pos: &str, id_def: &IdDef) -> Option<i32> {
+        let patterns = self.id_def_patterns(user_pos)?;
+        let preference = |pos: &str, id: i32| {
+            let parts: Vec<&str> = pos.split(',').collect();
+            let inflected = !matches!(parts.get(5), Some(&"基本形") | Some(&"*"));
+            let lexical = parts.get(6).is_some_and(|base| *base != "*");
+            (inflected, lexical, id)
+        };
+        let exact = patterns.iter().find_map(|pattern| {
+            let key_parts: Vec<&str> = pattern.split(',').collect();
+            id_def.iter()
+                .filter(|(pos, _)| {
+                    let parts: Vec<&str> = pos.split(',').collect();
+                    pattern_matches(&parts, &key_parts)
+                })
+                .min_by_key(|(pos, &id)| (preference(pos, id), pos.to_string()))
+                .map(|(_, &id)| id)
+        });
+        exact.or_else(|| patterns.iter().find_map(|pattern| {
+            let key_parts: Vec<&str> = pattern.split(',').collect();
+            id_def.iter()
+                .filter_map(|(pos, &id)| {
+                    let parts: Vec<&str> = pos.split(',').collect();
+                    let score = user_pos_score(&parts, &key_parts)?;
+                    // 記号はどの記号の品詞とも同じ点数になるため、一致した項目の数で区別する
+                    let fields = parts.iter().zip(&key_parts).filter(|(a, b)| **b != "*" && *a == *b).count();
+                    Some(((score, fields), std::cmp::Reverse((preference(pos, id), pos)), id))
+                })
+                .max_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)))
+                .map(|(_, _, id)| id)
+        }))
+    }
+
+    /// ユーザー辞書の品詞名に対応する、id.defの品詞
+    pub fn id_def_patterns(&self, user_pos: &str) -> Option<&[String]> {
+        self.user_to_id_def.get(user_pos).map(|v| v.as_slice())
+    }
+}
+
+/// マッピング作成
+/// 既定の対応表(pos_mapping.yaml)を用いる。
+pub fn create_pos_mapping() -> PosMapping {
+    PosMapping::from_yaml(DEFAULT_POS_MAPPING).expect("built-in POS mapping")
+}
+
+// id.defの品詞(parts)が、対応表の品詞(key_parts)の`*`以外の項目とすべて一致するか
+fn pattern_matches(parts: &[&str], key_parts: &[&str]) -> bool {
+    parts.len() == key_parts.len() && parts.iter().zip(key_parts).all(|(a, b)| *b == "*" || a == b)
+}
+
+// id.defの品詞(parts)と、対応表の品詞(key_parts)の一致の度合い
+// 対応表の品詞として選べない場合はNone
+fn user_pos_score(parts: &[&str], key_parts: &[&str]) -> Option<usize> {
+    // 特殊なケース（記号など）の処理
+    if parts[0] == "記号" || parts[0] == "補助記号" {
+        return if key_parts[0] == "記号" { Some(usize::MAX) } else { None };
+    }
+
+    let mut match_count = 0;
+
+    // 全項目のマッチングを試みる
+    for (i, (a, b)) in parts.iter().zip(key_parts.iter()).enumerate() {
+        if *b != "*" && *a == *b {
+            match_count += 1;
+        } else if *b == "*" && i < 2 {
+            // 大分類、中分類の「*」は任意の値に一致する（感動詞,*,…など）
+        } else if i < 2 { // 最初の2項目（品詞大分類、中分類）は必ずマッチする必要がある
+            return None;
+        } else {
+            // 後半の項目（活用型など）が一致しない場合
+            // 完全一致でなくても、部分的な一致を許容する
+            if a.contains(b) || b.contains(a) {
+                match_count += 1;
+            }
+        }
+    }
+
+    // 固有名詞の場合、より詳細なマッチングを要求
+    if parts.len() >= 2 && parts[0] == "名詞" && parts[1] == "固有名詞" && match_count < 4 {
+        return None;
+    }
+
+    // 動詞の活用型のマッチング
+    if parts[0] == "動詞" {
+        let verb_type = parts.get(4).unwrap_or(&"");
+        if verb_type_matches(verb_type, key_parts) {
+            match_count += 1;
+        }
+    }
+
+    Some(match_count)
+}
+
+/// hinshi_idからユーザー辞書の品詞の判定
+/// 一致の度合いが同じ場合は、対応表の品詞の文字列の順で先のものを選ぶ。
+pub fn get_user_pos_by_id(mapping: &mut PosMapping, id_def: &IdDef, hinshi_id: i32) -> Option<String> {
+    // キャッシュをチェック
+    if let Some(cached_pos) = mapping.id_to_user_pos_cache.get(&hinshi_id) {
+        return Some(cached_pos.clone());
+    }
+    let result = id_def.iter()
+        .filter(|(_, &id)| id == hinshi_id)
+        .map(|(pos, _)| pos)
+        .min()
+        .and_then(|pos| {
+            let parts: Vec<&str> = pos.split(',').collect();
+            mapping.id_def_to_user.iter()
+                .filter_map(|(key, value)| {
+                    let key_parts: Vec<&str> = key.split(',').collect();
+                    user_pos_score(&parts, &key_parts).map(|score| (score, key, value))
+                })
+                .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(a.1)))
+                .map(|(_, _, value)| value.clone())
+        });
+    // 結果をキャッシュに保存
+    if let Some(ref pos) = result {
+        mapping.id_to_user_pos_cache.insert(hinshi_id, pos.clone());
+    }
+
+    result
+}
+
+/// ユーザー辞書から品詞idの検索
+pub fn u_search_key(mapping: &mut PosMapping, id_def: &mut IdDef, hinshi_id: i32) -> Option<String> {
+    get_user_pos_by_id(mapping, id_def, hinshi_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id_def::read_id_def;
+
+    fn id_def() -> IdDef {
+        read_id_def(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/id.def")).unwrap().0
+    }
+
+    #[test]
+    fn builtin_mapping_is_valid() {
+        assert_eq!(create_pos_mapping().validate(&id_def()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn non_mozc_user_pos_is_rejected() {
+        let id_def = id_def();
+        for name in ["フィラー", "BOS/EOS", "動詞五段", "動詞ハ行四"] {
+            let mapping = PosMapping::from_yaml(&format!("\"{}\": \"感動詞,*,*,*,*,*,*\"", name)).unwrap();
+            assert_eq!(mapping.validate(&id_def), vec![format!("unknown user POS name: {}", name)]);
+        }
+    }
+
+    #[test]
+    fn user_pos_resolves_to_ipa_verbs() {
+        let id_def = id_def();
+        let mapping = create_pos_mapping();
+        let id = |name: &str| mapping.user_pos_to_id(name, &id_def);
+        assert_eq!(id("動詞カ行五段"), Some(21));
+        assert_eq!(id("動詞ハ行四段"), Some(20));
+        assert_eq!(id("動詞ワ行五段"), Some(31));
+        assert_eq!(id("動詞一段"), Some(14));
+        assert_eq!(id("動詞サ変"), Some(17));
+        assert_eq!(id("動詞ラ変"), Some(19));
+        assert_eq!(id("句読点"), Some(6));
+        assert_eq!(id("感動詞"), Some(3));
+        assert_eq!(id("短縮よみ"), None);
+    }
+
+    #[test]
+    fn raw_id_def_pattern_is_normalized() {
+        let id_def = id_def();
+        let mapping = PosMapping::from_yaml(concat!(
+            "動詞カ行五段: \"動詞,自立,*,*,五段・カ行イ音便,基本形,*\"\n",
+            "動詞ワ行五段: \"動詞,自立,*,*,五段・ワ行促音便,基本形,*\"\n",
+        )).unwrap();
+        assert_eq!(mapping.user_pos_to_id("動詞カ行五段", &id_def), Some(21));
+        assert_eq!(mapping.user_pos_to_id("動詞ワ行五段", &id_def), Some(31));
+    }
+
+    #[test]
+    fn inexact_pattern_is_resolved_by_score() {
+        let id_def = id_def();
+        let mapping = PosMapping::from_yaml("動詞一段: \"動詞,自立,*,*,一段,基本形,*,*\"").unwrap();
+        assert_eq!(mapping.user_pos_to_id("動詞一段", &id_def), Some(14));
+    }
+
+    #[test]
+    fn unmatched_pattern_is_rejected() {
+        let mapping = PosMapping::from_yaml("動詞カ行五段: \"動詞,一般,*,*,五段・カ行,*,*\"").unwrap();
+        assert_eq!(mapping.validate(&id_def()).len(), 1);
+    }
+
+    #[test]
+    fn mistyped_pattern_is_rejected() {
+        let mapping = PosMapping::from_yaml("動詞カ行五段: \"動詞,自立,*,*,五段・カ行イ音便,基本刑,*\"").unwrap();
+        assert_eq!(mapping.validate(&id_def()), ["動詞カ行五段: pattern matches no id.def line: 動詞,自立,*,*,五段・カ行,基本刑,*"]);
+    }
+}
@@ -0,0 +1,133 @@
+//! 辞書データの品詞からid.defの品詞への明示的な対応表(--pos-map)
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::id_def::{IdDef, normalize_id_def_pos, normalize_unidic_pos};
+
+// 対応表のキーを、読み込み処理が品詞IDの判定に用いる形にする
+// UniDic系の6項目の品詞のみ置き換え、それ以外(NEologdなど)はそのまま用いる。
+fn normalize_source_pos(source: &str) -> String {
+    let fields: Vec<&str> = source.split(',').collect();
+    if fields.len() == 6 {
+        normalize_unidic_pos(&fields)
+    } else {
+        source.to_string()
+    }
+}
+
+/// 対応表の1行
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PosOverride {
+    /// 対応表に書かれたid.defの品詞(IDで書かれた場合はIDの文字列)
+    pub target: String,
+    pub id: i32,
+}
+
+/// 辞書データの品詞の文字列と完全に一致した場合に、点数による判定より優先して用いる対応表
+/// sudachi/clsmap.yamlと同じく、辞書データの品詞をキーとし、id.defの品詞(またはID)を値とする。
+/// キーはSudachiDictなどに書かれたままの品詞で記述し、読み込み時に、読み込み処理と同じ置き換え(補助記号を記号にするなど)をする。
+#[derive(Clone, Debug, Default)]
+pub struct PosOverrides {
+    rules: HashMap<String, PosOverride>,
+}
+
+impl PosOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// YAMLの対応表を読み込み、値をid.defのIDに変換する
+    /// id.defの品詞は、read_id_defと同じ置き換えをした上で照合する。
+    pub fn from_yaml(text: &str, id_def: &IdDef) -> Result<Self, String> {
+        // 空のファイルやコメントのみのファイルは、空の対応表とする
+        let table: Option<serde_yaml::Mapping> = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+        let mut rules = HashMap::new();
+        let mut problems = Vec::new();
+        for (source, target) in table.iter().flatten() {
+            let source = source.as_str()
+                .ok_or_else(|| format!("source POS must be a string: {:?}", source))?;
+            let (target, id) = match target {
+                serde_yaml::Value::String(expr) => (expr.clone(), id_def.get(&normalize_id_def_pos(expr)).copied()),
+                serde_yaml::Value::Number(n) => {
+                    let id = n.as_i64().and_then(|n| i32::try_from(n).ok());
+                    (n.to_string(), id.filter(|id| id_def.values().any(|v| v == id)))
+                },
+                _ => return Err(format!("{}: expected an id.def POS or ID", source)),
+            };
+            match id {
+                Some(id) => { rules.insert(normalize_source_pos(source), PosOverride { target, id }); },
+                None => problems.push(format!("{}: not found in id.def: {}", source, target)),
+            }
+        }
+        if !problems.is_empty() {
+            return Err(problems.join("; "));
+        }
+        Ok(Self { rules })
+    }
+
+    /// 対応表のファイルを読み込む
+    /// id.defにない品詞やIDがあればエラーとする。
+    pub fn load(path: &Path, id_def: &IdDef) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        PosOverrides::from_yaml(&text, id_def)
+            .map_err(|message| Error::Config { path: path.to_path_buf(), message })
+    }
+
+    /// 辞書データの品詞の文字列に対応する行
+    pub fn get(&self, clsexpr: &str) -> Option<&PosOverride> {
+        self.rules.get(clsexpr)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 対応表の全ての行
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PosOverride)> {
+        self.rules.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id_def::PosResolver;
+
+    // 同梱のclsmap.yamlの値が、すべてid.defにあるものとする
+    fn clsmap_id_def(text: &str) -> IdDef {
+        let table: serde_yaml::Mapping = serde_yaml::from_str(text).unwrap();
+        let mut id_def = IdDef::new();
+        id_def.insert("名詞,普通名詞,*,*,*,*,*".to_string(), 0);
+        for target in table.values().filter_map(|v| v.as_str()) {
+            let next = id_def.len() as i32;
+            id_def.entry(normalize_id_def_pos(target)).or_insert(next);
+        }
+        id_def
+    }
+
+    #[test]
+    fn shipped_clsmap_rule_matches_raw_sudachi_pos() {
+        let text = include_str!("../clsmap.yaml");
+        let id_def = clsmap_id_def(text);
+        let interjection = id_def["感動詞,*,*,*,*,*,*"];
+        let overrides = PosOverrides::from_yaml(text, &id_def).unwrap();
+
+        // SudachiDictの「補助記号,ＡＡ,顔文字」は、読み込み時に「記号,ＡＡ,顔文字」になる
+        let pos = normalize_unidic_pos(&["補助記号", "ＡＡ", "顔文字", "*", "*", "*"]);
+        assert_eq!(overrides.get(&pos).map(|rule| rule.id), Some(interjection));
+
+        let mut resolver = PosResolver::new(id_def, 0);
+        resolver.set_overrides(overrides);
+        assert_eq!(resolver.resolve(&pos), interjection);
+    }
+
+    #[test]
+    fn non_unidic_keys_are_kept() {
+        let mut id_def = IdDef::new();
+        id_def.insert("名詞,固有名詞,一般,*,*,*,*".to_string(), 5);
+        let overrides = PosOverrides::from_yaml("名詞,固有名詞,一般,*,*,*,*: 5", &id_def).unwrap();
+        assert_eq!(overrides.get("名詞,固有名詞,一般,*,*,*,*").map(|rule| rule.id), Some(5));
+    }
+}
@@ -0,0 +1,702 @@
+//! 各辞書型式の読み込み
+
+use std::path::{Path, PathBuf};
+
+use std::fs::File;
+use std::io::Read;
+
+use regex::Regex;
+use csv::{Reader, ReaderBuilder, StringRecord};
+use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
+
+use crate::byhand::{ByhandDef, ByhandPos};
+use crate::cost::CostProfile;
+use crate::dictionary::{DictionaryData, DictionaryEntry, DictionaryKey};
+use crate::error::{Error, record_line};
+use crate::exclude::ExcludeList;
+use crate::id_def::{PosResolver, normalize_id_def_pos, normalize_unidic_pos};
+use crate::reject::{RejectReason, Rejects};
+use crate::word_policy::WordPolicy;
+use crate::utils::{BYHAND_COST, MOZC_USER_COST, convert_to_hiragana, unicode_escape_to_char};
+
+/// 辞書の型式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DictFormat {
+    Sudachi,
+    UtDict,
+    Neologd,
+    /// mecab-ipadic、NAIST-jdic
+    Ipadic,
+    /// UniDicのlex.csv
+    Unidic,
+    /// ByHand辞書(byhand/dict.csv)
+    Byhand,
+    /// Mozcのユーザー辞書型式(-Uの出力、Mozcの辞書ツールで書き出したもの)
+    MozcUser,
+}
+
+impl DictFormat {
+    /// --encodingが指定されなかった場合の文字コード
+    /// mecab-ipadic、NAIST-jdicの配布物はEUC-JPで、それ以外はUTF-8とする。
+    pub fn default_encoding(&self) -> InputEncoding {
+        match self {
+            DictFormat::Ipadic => InputEncoding::EucJp,
+            _ => InputEncoding::Utf8,
+        }
+    }
+}
+
+impl std::str::FromStr for DictFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sudachi" => Ok(DictFormat::Sudachi),
+            "utdict" => Ok(DictFormat::UtDict),
+            "neologd" => Ok(DictFormat::Neologd),
+            "ipadic" => Ok(DictFormat::Ipadic),
+            "unidic" => Ok(DictFormat::Unidic),
+            "byhand" => Ok(DictFormat::Byhand),
+            "mozc-user" => Ok(DictFormat::MozcUser),
+            _ => Err(format!("unknown dictionary format: {} (sudachi, utdict, neologd, ipadic, unidic, byhand, mozc-user)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for DictFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DictFormat::Sudachi => "sudachi",
+            DictFormat::UtDict => "utdict",
+            DictFormat::Neologd => "neologd",
+            DictFormat::Ipadic => "ipadic",
+            DictFormat::Unidic => "unidic",
+            DictFormat::Byhand => "byhand",
+            DictFormat::MozcUser => "mozc-user",
+        })
+    }
+}
+
+/// 入力ファイルの文字コード
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEncoding {
+    Utf8,
+    EucJp,
+    ShiftJis,
+}
+
+impl std::str::FromStr for InputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(InputEncoding::Utf8),
+            "euc-jp" | "eucjp" => Ok(InputEncoding::EucJp),
+            "shift_jis" | "shift-jis" | "sjis" => Ok(InputEncoding::ShiftJis),
+            _ => Err(format!("unknown encoding: {} (euc-jp, shift_jis, utf-8)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for InputEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InputEncoding::Utf8 => "utf-8",
+            InputEncoding::EucJp => "euc-jp",
+            InputEncoding::ShiftJis => "shift_jis",
+        })
+    }
+}
+
+/// --input FORMAT:PATH で指定される入力ファイル
+#[derive(Clone, Debug)]
+pub struct InputSpec {
+    pub format: DictFormat,
+    pub path: PathBuf,
+}
+
+impl std::str::FromStr for InputSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s.split_once(':')
+            .ok_or_else(|| format!("input must be FORMAT:PATH: {}", s))?;
+        if path.is_empty() {
+            return Err(format!("input path is empty: {}", s));
+        }
+        Ok(InputSpec { format: format.parse()?, path: PathBuf::from(path) })
+    }
+}
+
+impl std::fmt::Display for InputSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.format, self.path.display())
+    }
+}
+
+/// --priority sudachi,utdict,neologd のように、型式の優先順位をカンマ区切りで指定する
+#[derive(Clone, Debug, Default)]
+pub struct PriorityOrder(pub Vec<DictFormat>);
+
+impl std::str::FromStr for PriorityOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 空の場合は、入力の順とする
+        if s.trim().is_empty() {
+            return Ok(PriorityOrder::default());
+        }
+        s.split(',')
+            .map(|f| f.trim().parse())
+            .collect::<Result<Vec<_>, _>>()
+            .map(PriorityOrder)
+    }
+}
+
+impl std::fmt::Display for PriorityOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let formats: Vec<String> = self.0.iter().map(|format| format.to_string()).collect();
+        f.write_str(&formats.join(","))
+    }
+}
+
+impl PriorityOrder {
+    /// 入力ごとの優先順位を決める。
+    /// 型式の優先順位が先に比較され、同じ型式の場合は指定された順になる。
+    /// --priorityに含まれない型式は、含まれる型式よりも後になる。
+    pub fn ranks(&self, inputs: &[InputSpec]) -> Vec<usize> {
+        let format_rank = |f: DictFormat| self.0.iter().position(|&p| p == f).unwrap_or(self.0.len());
+        let mut order: Vec<usize> = (0..inputs.len()).collect();
+        order.sort_by_key(|&i| (format_rank(inputs[i].format), i));
+        let mut ranks = vec![0; inputs.len()];
+        for (rank, i) in order.into_iter().enumerate() {
+            ranks[i] = rank;
+        }
+        ranks
+    }
+}
+
+/// 読み込み時の設定
+/// 既定値では、地名と記号を含めず、不正な行は読み飛ばす。
+#[derive(Clone, Copy, Default)]
+pub struct ReadOptions<'a> {
+    /// ユーザー辞書型式で出力する
+    pub user_dict_flag: bool,
+    /// 地名を含める
+    pub chimei_flag: bool,
+    /// 記号を含める
+    pub symbol_flag: bool,
+    /// 入力元の優先順位(小さいほど優先)
+    pub priority: usize,
+    /// 不正な行があれば、読み飛ばさずにエラーとする
+    pub strict: bool,
+    /// 入力ファイルの文字コード(Noneの場合は型式ごとの既定)
+    pub encoding: Option<InputEncoding>,
+    /// 除外する単語の一覧
+    pub exclude: Option<&'a ExcludeList>,
+    /// 英語、全角英語、固有名詞の扱い
+    pub policy: WordPolicy,
+    /// コストの変換方法
+    pub cost: CostProfile,
+}
+
+impl ReadOptions<'_> {
+    /// 型式に応じた入力ファイルの文字コード
+    pub fn encoding_for(&self, format: DictFormat) -> InputEncoding {
+        self.encoding.unwrap_or(format.default_encoding())
+    }
+}
+
+/// 読み込み結果の集計
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReadStats {
+    /// 読み込んだ行数
+    pub records: usize,
+    /// 不正なため読み飛ばした行数
+    pub bad_lines: usize,
+}
+
+// 不正な行は、strictの場合はエラーとして返し、それ以外は読み飛ばして数える。
+macro_rules! skip_on_error {
+    ($stats:expr, $opts:expr, $rejects:expr, $result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(err) => {
+                if $opts.strict {
+                    return Err(err);
+                }
+                $stats.bad_lines += 1;
+                $rejects.error(&err)?;
+                continue;
+            }
+        }
+    };
+}
+
+// CRLFをLFに置き換えながら読み込む
+// csvはCRLFも行末として扱うが、CRLFで終わる行では行番号が進まず、エラーや除外の行番号がずれるため。
+struct CrlfReader<R> {
+    inner: R,
+    // 直前の読み込みの最後がCRだった
+    pending_cr: bool,
+    // CRの次に読んだ、まだ返していない1バイト
+    held: Option<u8>,
+}
+
+impl<R> CrlfReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, pending_cr: false, held: None }
+    }
+}
+
+impl<R: Read> Read for CrlfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.held.take() {
+                Some(b'\r') => self.pending_cr = true,
+                Some(byte) => {
+                    buf[0] = byte;
+                    return Ok(1);
+                },
+                None => {},
+            }
+            // 読み込みの境界にあったCRは、次の1バイトを見てから返す
+            if self.pending_cr {
+                let mut next = [0u8; 1];
+                let n = self.inner.read(&mut next)?;
+                self.pending_cr = false;
+                if n == 1 && next[0] == b'\n' {
+                    buf[0] = b'\n';
+                    return Ok(1);
+                }
+                if n == 1 {
+                    self.held = Some(next[0]);
+                }
+                buf[0] = b'\r';
+                return Ok(1);
+            }
+            let end = self.inner.read(buf)?;
+            if end == 0 {
+                return Ok(0);
+            }
+            let mut out = 0;
+            let mut i = 0;
+            while i < end {
+                if buf[i] == b'\r' {
+                    if i + 1 == end {
+                        self.pending_cr = true;
+                        break;
+                    }
+                    if buf[i + 1] == b'\n' {
+                        i += 1;
+                        continue;
+                    }
+                }
+                buf[out] = buf[i];
+                out += 1;
+                i += 1;
+            }
+            if out > 0 {
+                return Ok(out);
+            }
+        }
+    }
+}
+
+type CsvReader = Reader<CrlfReader<DecodeReaderBytes<File, Vec<u8>>>>;
+
+// 区切り文字と文字コードを指定して、ヘッダーなしのCSVファイルを開く
+// UTF-8以外はUTF-8へ変換しながら読み込む。BOMは取り除き、改行はCRLFとLFのどちらでもよい。
+fn open_csv(path: &Path, delimiter: u8, encoding: InputEncoding) -> Result<CsvReader, Error> {
+    open_csv_with(path, delimiter, encoding, true)
+}
+
+// quotingがfalseの場合は、"を通常の文字として扱う
+fn open_csv_with(path: &Path, delimiter: u8, encoding: InputEncoding, quoting: bool) -> Result<CsvReader, Error> {
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let decoder = match encoding {
+        // UTF-8はそのまま渡し、不正なバイト列はCSVの読み込みでエラーとする
+        InputEncoding::Utf8 => DecodeReaderBytesBuilder::new().utf8_passthru(true).strip_bom(true).build(file),
+        InputEncoding::EucJp => DecodeReaderBytesBuilder::new().encoding(Some(encoding_rs::EUC_JP)).strip_bom(true).build(file),
+        InputEncoding::ShiftJis => DecodeReaderBytesBuilder::new().encoding(Some(encoding_rs::SHIFT_JIS)).strip_bom(true).build(file),
+    };
+    Ok(ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .quoting(quoting)
+        .from_reader(CrlfReader::new(decoder)))
+}
+
+// 読み込んだ行が、必要な列数を満たしているかを確認する
+fn check_record(path: &Path, result: Result<StringRecord, csv::Error>, columns: usize) -> Result<StringRecord, Error> {
+    let record = result.map_err(|e| Error::csv(path, e))?;
+    if record.len() < columns {
+        return Err(Error::Columns {
+            path: path.to_path_buf(),
+            line: record_line(&record),
+            expected: columns,
+            found: record.len(),
+        });
+    }
+    Ok(record)
+}
+
+// 数値の列を読み込む
+fn parse_field<T: std::str::FromStr>(path: &Path, record: &StringRecord, column: usize, name: &'static str) -> Result<T, Error> {
+    record[column].trim().parse::<T>()
+        .map_err(|_| Error::field(path, record, column, name, "not a number"))
+}
+
+// Unicode Escapeを変換する。textはcolumnの列の値を加工したもの。
+fn unescape_field(path: &Path, record: &StringRecord, column: usize, name: &'static str, text: &str) -> Result<String, Error> {
+    unicode_escape_to_char(text).map_err(|reason| Error::field(path, record, column, name, reason))
+}
+
+// エントリーを追加する
+// ユーザー辞書型式の場合、品詞IDからユーザー辞書の品詞名を判定する。判定できない場合は品詞IDをそのまま使う。
+// 除外リストに一致したエントリー、または重複して残らなかったエントリーを、その理由とともに返す。
+// raw_costは入力元のコストで、costはそれを変換したもの。
+fn add_entry(dict_data: &mut DictionaryData, resolver: &mut PosResolver, key: DictionaryKey, cost: i32, raw_cost: i32, line: u64, opts: &ReadOptions) -> Option<(RejectReason, DictionaryEntry)> {
+    if opts.exclude.is_some_and(|exclude| exclude.matches(&key.yomi, &key.surface)) {
+        return Some((RejectReason::Excluded, DictionaryEntry { key, cost, raw_cost, pos: "".to_string(), priority: opts.priority, line }));
+    }
+    let dropped = if opts.user_dict_flag {
+        let pos = resolver.user_pos(key.hinshi_id)
+            .unwrap_or_else(|| key.hinshi_id.to_string());
+        dict_data.add(DictionaryEntry { key, cost, raw_cost, pos, priority: opts.priority, line }, true)
+    } else {
+        dict_data.add(DictionaryEntry { key, cost, raw_cost, pos: "".to_string(), priority: opts.priority, line }, false)
+    };
+    dropped.map(|entry| (RejectReason::Duplicate, entry))
+}
+
+// UniDic系の品詞(品詞大分類、中分類、小分類、細分類、活用型、活用形)を、id.defと比較する品詞の文字列にする
+// startは品詞大分類の列番号
+fn unidic_pos(data: &StringRecord, start: usize) -> String {
+    let fields: Vec<&str> = (start..start + 6).map(|i| &data[i]).collect();
+    normalize_unidic_pos(&fields)
+}
+
+/// SudachiDict読み込み
+pub fn sudachi_read_csv(path: &Path, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    let ReadOptions { chimei_flag, symbol_flag, .. } = *opts;
+    let mut stats = ReadStats::default();
+    let mut reader = open_csv(path, b',', opts.encoding_for(DictFormat::Sudachi))?;
+    let kana_check = Regex::new(r"^[ぁ-ゖァ-ヺ]+$").unwrap();
+    let eisuu_check = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
+    for result in reader.records() {
+        stats.records += 1;
+        let data = skip_on_error!(stats, opts, rejects, check_record(path, result, 12));
+        let s3 = &data[5].replace("補助記号", "記号"); //.replace("空白","記号");
+        if ! symbol_flag && &data[11] == "キゴウ" && s3.contains("記号") { rejects.record(RejectReason::Symbol, path, &data)?; continue };
+        if ! symbol_flag && s3 == "空白" { rejects.record(RejectReason::Space, path, &data)?; continue };
+        let d = unidic_pos(&data, 5);
+        if let Some(reason) = opts.policy.check(&data[4], &d, symbol_flag) { rejects.record(reason, path, &data)?; continue };
+        if ! kana_check.is_match(&data[11]) { rejects.record(RejectReason::NonKanaReading, path, &data)?; continue };
+        // 地名を含む場合、オプション指定がなければ、英数のみの地名だけ残し、それ以外は省く。
+        if data[7].contains("地名") && ! eisuu_check.is_match(&data[0]) && ! chimei_flag { rejects.record(RejectReason::Place, path, &data)?; continue };
+        let cost = skip_on_error!(stats, opts, rejects, parse_field::<i32>(path, &data, 3, "cost"));
+        let _yomi: String = convert_to_hiragana(&data[11]);
+        let s1 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 11, "yomi", &_yomi));
+        let s2 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 4, "surface", &data[4]));
+        let hinshi_id = resolver.resolve(&d);
+        let key = DictionaryKey { yomi: s1, surface: s2, hinshi_id };
+        if let Some((reason, dropped)) = add_entry(dict_data, resolver, key, opts.cost.apply(cost), cost, record_line(&data), opts) {
+            rejects.entry(reason, &dropped)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// UniDic(unidic-cwj、unidic-csj)のlex.csv読み込み
+/// 表層形,左文脈ID,右文脈ID,コスト,品詞大分類,中分類,小分類,細分類,活用型,活用形,語彙素読み,語彙素,書字形出現形,発音形出現形,...,仮名形出現形,...
+/// 読みには仮名形出現形(25列目)を用いる。発音形は長音記号を含む(トーキョー)ため、仮名形のない古い版でのみ用いる。
+pub fn unidic_read_csv(path: &Path, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    let ReadOptions { chimei_flag, symbol_flag, .. } = *opts;
+    let mut stats = ReadStats::default();
+    let mut reader = open_csv(path, b',', opts.encoding_for(DictFormat::Unidic))?;
+    let kana_check = Regex::new(r"^[ぁ-ゖァ-ヺー]+$").unwrap();
+    let eisuu_check = Regex::new(r"^[a-zA-Z0-9]+$").unwrap();
+    for result in reader.records() {
+        stats.records += 1;
+        let data = skip_on_error!(stats, opts, rejects, check_record(path, result, 14));
+        // 仮名形出現形、なければ発音形出現形
+        let yomi_column = match data.get(24) {
+            Some(kana) if !kana.is_empty() && kana != "*" => 24,
+            _ => 13,
+        };
+        let s3 = &data[4].replace("補助記号", "記号");
+        if ! symbol_flag && &data[yomi_column] == "キゴウ" && s3.contains("記号") { rejects.record(RejectReason::Symbol, path, &data)?; continue };
+        if ! symbol_flag && s3 == "空白" { rejects.record(RejectReason::Space, path, &data)?; continue };
+        let d = unidic_pos(&data, 4);
+        if let Some(reason) = opts.policy.check(&data[0], &d, symbol_flag) { rejects.record(reason, path, &data)?; continue };
+        if ! kana_check.is_match(&data[yomi_column]) { rejects.record(RejectReason::NonKanaReading, path, &data)?; continue };
+        // 地名を含む場合、オプション指定がなければ、英数のみの地名だけ残し、それ以外は省く。
+        if data[6].contains("地名") && ! eisuu_check.is_match(&data[0]) && ! chimei_flag { rejects.record(RejectReason::Place, path, &data)?; continue };
+        let cost = skip_on_error!(stats, opts, rejects, parse_field::<i32>(path, &data, 3, "cost"));
+        let _yomi: String = convert_to_hiragana(&data[yomi_column]);
+        let s1 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, yomi_column, "yomi", &_yomi));
+        let s2 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 0, "surface", &data[0]));
+        let hinshi_id = resolver.resolve(&d);
+        let key = DictionaryKey { yomi: s1, surface: s2, hinshi_id };
+        if let Some((reason, dropped)) = add_entry(dict_data, resolver, key, opts.cost.apply(cost), cost, record_line(&data), opts) {
+            rejects.entry(reason, &dropped)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// ByHand辞書読み込み
+/// 読み、表記、品詞のタブ区切りで、#で始まる行はコメントとして読み飛ばす。
+/// 品詞は、同じディレクトリのdef.yamlでid.defの品詞に展開する。
+pub fn byhand_read_csv(path: &Path, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    let def = ByhandDef::load(&path.with_file_name("def.yaml"))?;
+    let mut stats = ReadStats::default();
+    let mut reader = open_csv(path, b'\t', opts.encoding_for(DictFormat::Byhand))?;
+    for result in reader.records() {
+        let data = match result {
+            Ok(data) if data.get(0).is_some_and(|f| f.trim_start().starts_with('#')) => continue,
+            result => result,
+        };
+        stats.records += 1;
+        let data = skip_on_error!(stats, opts, rejects, check_record(path, data, 3));
+        let expanded = skip_on_error!(stats, opts, rejects, def.expand_spec(&data[2])
+            .map_err(|reason| Error::field(path, &data, 2, "pos", reason)));
+        let yomi = convert_to_hiragana(&data[0]);
+        for ByhandPos { pos, strip } in expanded {
+            let s1 = yomi.strip_suffix(strip).unwrap_or(&yomi).to_string();
+            let s2 = data[1].strip_suffix(strip).unwrap_or(&data[1]).to_string();
+            let hinshi_id = resolver.resolve(&normalize_id_def_pos(pos));
+            let key = DictionaryKey { yomi: s1, surface: s2, hinshi_id };
+            if let Some((reason, dropped)) = add_entry(dict_data, resolver, key, BYHAND_COST, BYHAND_COST, record_line(&data), opts) {
+                rejects.entry(reason, &dropped)?;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Mozcのユーザー辞書型式の読み込み
+/// 読み、表記、品詞名、コメントのタブ区切りで、#で始まる行はコメントとして読み飛ばす。
+/// 品詞名は、ユーザー辞書の品詞の対応表を逆に引いてid.defのIDにする。
+/// 表記に"を含むことがあるので、CSVの引用符としては扱わない。
+/// 読みには〜や数字なども使われるので、UtDictと同じく仮名を含むかのみを確かめる。
+pub fn mozc_user_read_csv(path: &Path, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    let mut stats = ReadStats::default();
+    let mut reader = open_csv_with(path, b'\t', opts.encoding_for(DictFormat::MozcUser), false)?;
+    let kana_check = Regex::new(r"[ぁ-ゖァ-ヺ]").unwrap();
+    for result in reader.records() {
+        let data = match result {
+            Ok(data) if data.get(0).is_some_and(|f| f.trim_start().starts_with('#')) => continue,
+            result => result,
+        };
+        stats.records += 1;
+        let data = skip_on_error!(stats, opts, rejects, check_record(path, data, 3));
+        if ! kana_check.is_match(&data[0]) { rejects.record(RejectReason::NonKanaReading, path, &data)?; continue };
+        let hinshi_id = match resolver.user_pos_id(&data[2]) {
+            Some(id) => id,
+            None => { rejects.record(RejectReason::UnknownUserPos, path, &data)?; continue },
+        };
+        let key = DictionaryKey { yomi: convert_to_hiragana(&data[0]), surface: data[1].to_string(), hinshi_id };
+        if let Some((reason, dropped)) = add_entry(dict_data, resolver, key, MOZC_USER_COST, MOZC_USER_COST, record_line(&data), opts) {
+            rejects.entry(reason, &dropped)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// UtDict読み込み
+pub fn utdict_read_csv(path: &Path, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    let ReadOptions { chimei_flag, symbol_flag, .. } = *opts;
+    let mut stats = ReadStats::default();
+    let mut reader = open_csv(path, b'\t', opts.encoding_for(DictFormat::UtDict))?;
+    let kana_check = Regex::new(r"[ぁ-ゖァ-ヺ]").unwrap();
+    for result in reader.records() {
+        stats.records += 1;
+        let data = skip_on_error!(stats, opts, rejects, check_record(path, result, 5));
+        if ! kana_check.is_match(&data[0]) { rejects.record(RejectReason::NonKanaReading, path, &data)?; continue };
+        let hinshi_id = skip_on_error!(stats, opts, rejects, parse_field::<i32>(path, &data, 1, "hinshi_id"));
+        let clsexpr = resolver.id_def_pos(hinshi_id);
+        if let Some(reason) = opts.policy.check(&data[0], &clsexpr, symbol_flag) { rejects.record(reason, path, &data)?; continue };
+        if clsexpr.contains("地名") && ! chimei_flag { rejects.record(RejectReason::Place, path, &data)?; continue }
+        let cost = skip_on_error!(stats, opts, rejects, parse_field::<i32>(path, &data, 3, "cost"));
+        let _yomi: String = convert_to_hiragana(&data[0]);
+        let s1 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 0, "yomi", &_yomi));
+        let s2 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 4, "surface", &data[4]));
+        let key = DictionaryKey { yomi: s1, surface: s2, hinshi_id };
+        if let Some((reason, dropped)) = add_entry(dict_data, resolver, key, opts.cost.apply(cost), cost, record_line(&data), opts) {
+            rejects.entry(reason, &dropped)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// Neologd読み込み
+pub fn neologd_read_csv(path: &Path, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    let ReadOptions { chimei_flag, symbol_flag, .. } = *opts;
+    let mut stats = ReadStats::default();
+    let mut reader = open_csv(path, b',', opts.encoding_for(DictFormat::Neologd))?;
+    let kana_check = Regex::new(r"[ぁ-ゖァ-ヺ]").unwrap();
+    for result in reader.records() {
+        stats.records += 1;
+        let data = skip_on_error!(stats, opts, rejects, check_record(path, result, 12));
+        if &data[11] == "キゴウ" && data[10].contains("記号") { rejects.record(RejectReason::Symbol, path, &data)?; continue };
+        if &data[4] == "空白" { rejects.record(RejectReason::Space, path, &data)?; continue };
+        let s3 = &data[4];//.replace("補助記号", "記号"); //.replace("空白","記号");
+        // 固有名詞などはそのまま保持
+        let s4 = if &data[4] == "名詞" && &data[5] == "一般" {
+            "普通名詞"
+        } else {
+            &data[5]
+        };
+        let s5 = &data[9];//.replace("形-", "形,");
+        let d: String = format!("{},{},{},{},{},{}", s3, s4, &data[6], &data[7], &data[8], s5);
+        if let Some(reason) = opts.policy.check(&data[0], &d, symbol_flag) { rejects.record(reason, path, &data)?; continue };
+        if ! kana_check.is_match(&data[11]) { rejects.record(RejectReason::NonKanaReading, path, &data)?; continue };
+        if ! chimei_flag && data[6].contains("地域") { rejects.record(RejectReason::Place, path, &data)?; continue };
+        let cost = skip_on_error!(stats, opts, rejects, parse_field::<i32>(path, &data, 3, "cost"));
+        let _yomi: String = convert_to_hiragana(&data[11]);
+        let s1 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 11, "yomi", &_yomi));
+        let s2 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 0, "surface", &data[0]));
+        let hinshi_id = resolver.resolve(&d);
+        let key = DictionaryKey { yomi: s1, surface: s2, hinshi_id };
+        if let Some((reason, dropped)) = add_entry(dict_data, resolver, key, opts.cost.apply(cost), cost, record_line(&data), opts) {
+            rejects.entry(reason, &dropped)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// mecab-ipadic、NAIST-jdic読み込み
+/// 表層形,左文脈ID,右文脈ID,コスト,品詞,品詞細分類1,品詞細分類2,品詞細分類3,活用型,活用形,原形,読み,発音
+/// 品詞はMozcのid.defと同じIPA品詞体系なので、id.defと同じ置き換えをして比較する。
+/// 原形まで含めた品詞がid.defにあれば、そちらを優先する。
+pub fn ipadic_read_csv(path: &Path, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    let ReadOptions { chimei_flag, symbol_flag, .. } = *opts;
+    let mut stats = ReadStats::default();
+    let mut reader = open_csv(path, b',', opts.encoding_for(DictFormat::Ipadic))?;
+    let kana_check = Regex::new(r"[ぁ-ゖァ-ヺ]").unwrap();
+    for result in reader.records() {
+        stats.records += 1;
+        let data = skip_on_error!(stats, opts, rejects, check_record(path, result, 12));
+        if ! symbol_flag && &data[11] == "キゴウ" && &data[4] == "記号" { rejects.record(RejectReason::Symbol, path, &data)?; continue };
+        if ! symbol_flag && &data[5] == "空白" { rejects.record(RejectReason::Space, path, &data)?; continue };
+        let pos = format!("{},{},{},{},{},{}", &data[4], &data[5], &data[6], &data[7], &data[8], &data[9]);
+        if let Some(reason) = opts.policy.check(&data[0], &pos, symbol_flag) { rejects.record(reason, path, &data)?; continue };
+        if ! kana_check.is_match(&data[11]) { rejects.record(RejectReason::NonKanaReading, path, &data)?; continue };
+        if ! chimei_flag && &data[6] == "地域" { rejects.record(RejectReason::Place, path, &data)?; continue };
+        let cost = skip_on_error!(stats, opts, rejects, parse_field::<i32>(path, &data, 3, "cost"));
+        let _yomi: String = convert_to_hiragana(&data[11]);
+        let s1 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 11, "yomi", &_yomi));
+        let s2 = skip_on_error!(stats, opts, rejects, unescape_field(path, &data, 0, "surface", &data[0]));
+        let lexical = normalize_id_def_pos(&format!("{},{}", pos, &data[10]));
+        let d = if resolver.id_def.contains_key(&lexical) {
+            lexical
+        } else {
+            normalize_id_def_pos(&format!("{},*", pos))
+        };
+        let hinshi_id = resolver.resolve(&d);
+        let key = DictionaryKey { yomi: s1, surface: s2, hinshi_id };
+        if let Some((reason, dropped)) = add_entry(dict_data, resolver, key, opts.cost.apply(cost), cost, record_line(&data), opts) {
+            rejects.entry(reason, &dropped)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// 入力ファイルを、その型式に応じて読み込み、dict_dataへ追加する。
+/// 除外された行は、理由とともにrejectsに記録される。
+pub fn read_dictionary(input: &InputSpec, resolver: &mut PosResolver, dict_data: &mut DictionaryData, opts: &ReadOptions, rejects: &mut Rejects) -> Result<ReadStats, Error> {
+    rejects.register_source(opts.priority, &input.path);
+    match input.format {
+        DictFormat::Sudachi => sudachi_read_csv(&input.path, resolver, dict_data, opts, rejects),
+        DictFormat::UtDict => utdict_read_csv(&input.path, resolver, dict_data, opts, rejects),
+        DictFormat::Neologd => neologd_read_csv(&input.path, resolver, dict_data, opts, rejects),
+        DictFormat::Ipadic => ipadic_read_csv(&input.path, resolver, dict_data, opts, rejects),
+        DictFormat::Unidic => unidic_read_csv(&input.path, resolver, dict_data, opts, rejects),
+        DictFormat::Byhand => byhand_read_csv(&input.path, resolver, dict_data, opts, rejects),
+        DictFormat::MozcUser => mozc_user_read_csv(&input.path, resolver, dict_data, opts, rejects),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_file;
+
+    // 1回の読み込みで、最大chunkバイトずつ返す
+    struct Chunked<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.data.len().min(self.chunk).min(buf.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    // 大きさbufの領域で、最後まで読み込む
+    fn read_all(data: &[u8], chunk: usize, buf: usize) -> Vec<u8> {
+        let mut reader = CrlfReader::new(Chunked { data, chunk });
+        let mut out = Vec::new();
+        let mut buffer = vec![0u8; buf];
+        loop {
+            let n = reader.read(&mut buffer).unwrap();
+            if n == 0 {
+                return out;
+            }
+            out.extend_from_slice(&buffer[..n]);
+        }
+    }
+
+    #[test]
+    fn crlf_becomes_lf_at_any_boundary() {
+        let data = b"a\r\nb\rc\r\r\nd\r\n\r";
+        for chunk in 1..=data.len() {
+            for buf in 1..=data.len() + 1 {
+                assert_eq!(read_all(data, chunk, buf), b"a\nb\rc\r\nd\n\r", "chunk {}, buf {}", chunk, buf);
+            }
+        }
+    }
+
+    #[test]
+    fn crlf_keeps_csv_line_numbers() {
+        let data = b"a,1\r\nb,2\r\n\"c\r\nd\",3\r\ne,4\r\n";
+        let mut reader = ReaderBuilder::new().has_headers(false).from_reader(CrlfReader::new(Chunked { data, chunk: 3 }));
+        let lines: Vec<(String, u64)> = reader.records()
+            .map(|r| r.unwrap())
+            .map(|r| (r[0].to_string(), record_line(&r)))
+            .collect();
+        assert_eq!(lines, [("a".to_string(), 1), ("b".to_string(), 2), ("c\nd".to_string(), 3), ("e".to_string(), 5)]);
+    }
+
+    #[test]
+    fn mozc_user_readings_only_need_kana() {
+        let path = temp_file("mozc_user.txt", concat!(
+            "ぶいつー\tV2\t名詞\t\n",
+            "〜ですわ\t〜ですわ\t名詞\t\n",
+            "なかぐろ・てん\t中黒・点\t名詞\t\n",
+            "ｇｏする\tGoする\t名詞\t\n",
+            "えーびーしー2\tABC2\t名詞\t\n",
+            "abc\tABC\t名詞\t\n",
+            "123\t百二十三\t名詞\t\n",
+        ));
+        let mut resolver = PosResolver::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/id.def")).unwrap();
+        let mut dict_data = DictionaryData::new();
+        let mut rejects = Rejects::new();
+        let opts = ReadOptions { strict: true, ..Default::default() };
+        let stats = mozc_user_read_csv(&path, &mut resolver, &mut dict_data, &opts, &mut rejects).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(stats.records, 7);
+        assert_eq!(rejects.count(RejectReason::NonKanaReading), 2);
+        assert_eq!(rejects.total(), 2);
+    }
+}
@@ -0,0 +1,175 @@
+//! 読み込み時に除外された行の記録
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use csv::StringRecord;
+
+use crate::dictionary::DictionaryEntry;
+use crate::error::{Error, record_line};
+
+/// 除外の理由
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RejectReason {
+    /// 不正な行(列が足りない、数値として読めないなど)
+    ParseError,
+    /// 読みが仮名ではない
+    NonKanaReading,
+    /// 記号
+    Symbol,
+    /// 空白
+    Space,
+    /// 英語(ASCIIと記号のみの表記)
+    Alphabet,
+    /// 全角英語、半角カナ(全角・半角形と記号のみの表記)
+    Fullwidth,
+    /// 固有名詞(--no-proper)
+    Proper,
+    /// 地名
+    Place,
+    /// 読み、表記、品詞IDが同じエントリーが既にある
+    Duplicate,
+    /// 除外リスト(exclude.txt)に一致した
+    Excluded,
+    /// ユーザー辞書の品詞名がid.defの品詞に対応付けられない
+    UnknownUserPos,
+    /// Mozcのシステム辞書(--baseline)に既にある
+    Baseline,
+    /// ユーザー辞書の品詞名が、user_dictionary.db(--user-db)の品詞にない
+    NoUserDbPos,
+}
+
+impl RejectReason {
+    /// 集計を表示する順
+    pub const ALL: [RejectReason; 13] = [
+        RejectReason::ParseError,
+        RejectReason::NonKanaReading,
+        RejectReason::Symbol,
+        RejectReason::Space,
+        RejectReason::Alphabet,
+        RejectReason::Fullwidth,
+        RejectReason::Proper,
+        RejectReason::Place,
+        RejectReason::Duplicate,
+        RejectReason::Excluded,
+        RejectReason::UnknownUserPos,
+        RejectReason::Baseline,
+        RejectReason::NoUserDbPos,
+    ];
+
+    /// rejects.tsvに出力される理由のコード
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectReason::ParseError => "parse_error",
+            RejectReason::NonKanaReading => "non_kana_reading",
+            RejectReason::Symbol => "symbol",
+            RejectReason::Space => "space",
+            RejectReason::Alphabet => "alphabet",
+            RejectReason::Fullwidth => "fullwidth",
+            RejectReason::Proper => "proper",
+            RejectReason::Place => "place",
+            RejectReason::Duplicate => "duplicate",
+            RejectReason::Excluded => "excluded",
+            RejectReason::UnknownUserPos => "unknown_user_pos",
+            RejectReason::Baseline => "baseline",
+            RejectReason::NoUserDbPos => "no_user_db_pos",
+        }
+    }
+}
+
+/// 除外された行の記録
+/// 理由ごとの件数を数え、出力先が指定されていれば、理由、ファイル名、行番号、行の内容をタブ区切りで書き出す。
+/// 重複や除外リスト、Mozcのシステム辞書などで除外されたエントリーは、元の行の代わりに読み、表記、品詞ID、コストを書き出す。
+pub struct Rejects {
+    writer: Option<(PathBuf, BufWriter<File>)>,
+    counts: HashMap<RejectReason, usize>,
+    sources: HashMap<usize, PathBuf>,
+}
+
+impl Default for Rejects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rejects {
+    /// 件数のみを数える
+    pub fn new() -> Self {
+        Self {
+            writer: None,
+            counts: HashMap::new(),
+            sources: HashMap::new(),
+        }
+    }
+
+    /// 件数を数え、除外された行をpathへ書き出す
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|e| Error::io(path, e))?;
+        Ok(Self {
+            writer: Some((path.to_path_buf(), BufWriter::new(file))),
+            ..Self::new()
+        })
+    }
+
+    /// 入力元の優先順位と、そのファイル名を対応付ける
+    /// 重複で置き換えられたエントリーのファイル名を出力するために用いる。
+    pub fn register_source(&mut self, priority: usize, path: &Path) {
+        self.sources.insert(priority, path.to_path_buf());
+    }
+
+    /// 理由ごとの件数
+    pub fn count(&self, reason: RejectReason) -> usize {
+        self.counts.get(&reason).copied().unwrap_or(0)
+    }
+
+    /// 除外された件数の合計
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    fn write<'a>(&mut self, reason: RejectReason, path: &Path, line: u64, fields: impl Iterator<Item = &'a str>) -> Result<(), Error> {
+        *self.counts.entry(reason).or_default() += 1;
+        if let Some((out, writer)) = &mut self.writer {
+            let fields: Vec<&str> = fields.collect();
+            writeln!(writer, "{}\t{}\t{}\t{}", reason.code(), path.display(), line, fields.join("\t"))
+                .map_err(|e| Error::io(out, e))?;
+        }
+        Ok(())
+    }
+
+    /// フィルターで除外された行を記録する
+    pub fn record(&mut self, reason: RejectReason, path: &Path, record: &StringRecord) -> Result<(), Error> {
+        self.write(reason, path, record_line(record), record.iter())
+    }
+
+    /// 不正なため読み飛ばした行を記録する
+    pub fn error(&mut self, err: &Error) -> Result<(), Error> {
+        let message = err.to_string();
+        self.write(RejectReason::ParseError, err.path(), err.line().unwrap_or(0), std::iter::once(message.as_str()))
+    }
+
+    /// 重複や除外リスト、Mozcのシステム辞書などで除外されたエントリーを記録する
+    pub fn entry(&mut self, reason: RejectReason, entry: &DictionaryEntry) -> Result<(), Error> {
+        let path = self.sources.get(&entry.priority).cloned().unwrap_or_default();
+        let (hinshi_id, cost) = (entry.key.hinshi_id.to_string(), entry.cost.to_string());
+        let fields = [entry.key.yomi.as_str(), entry.key.surface.as_str(), hinshi_id.as_str(), cost.as_str()];
+        self.write(reason, &path, entry.line, fields.into_iter())
+    }
+
+    /// 書き出しを完了する
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if let Some((out, writer)) = &mut self.writer {
+            writer.flush().map_err(|e| Error::io(out, e))?;
+        }
+        Ok(())
+    }
+
+    /// 理由ごとの件数を標準エラー出力へ表示する
+    pub fn print_summary(&self) {
+        for reason in RejectReason::ALL {
+            eprintln!("rejected {}: {}", reason.code(), self.count(reason));
+        }
+    }
+}
@@ -0,0 +1,410 @@
+//! Mozcのユーザー辞書ファイル(user_dictionary.db)
+//!
+//! MozcのUserDictionaryStorage(src/protocol/user_dictionary_storage.proto)のうち、
+//! 辞書の作成に必要な項目のみを定義する。
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use prost::{DecodeError, Message};
+use prost::encoding::{WireType, decode_key, decode_varint};
+
+use crate::dictionary::{MAX_USER_DICT_ENTRIES, DictionaryData, DictionaryEntry, SortOrder, split_by_yomi};
+use crate::error::Error;
+use crate::pos_mapping::MOZC_USER_POS;
+use crate::utils::config_home;
+
+// Mozcのユーザー辞書ファイルの名前
+const USER_DB_FILE: &str = "user_dictionary.db";
+
+// UserDictionaryStorage.dictionariesのフィールド番号
+const DICTIONARIES_TAG: u32 = 2;
+
+/// UserDictionary.Entry
+#[derive(Clone, PartialEq, Message)]
+pub struct UserDictionaryEntry {
+    /// 読み
+    #[prost(string, optional, tag = "1")]
+    pub key: Option<String>,
+    /// 表記
+    #[prost(string, optional, tag = "2")]
+    pub value: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub comment: Option<String>,
+    /// 品詞(UserDictionary.PosType)
+    #[prost(int32, optional, tag = "5")]
+    pub pos: Option<i32>,
+}
+
+/// UserDictionary
+#[derive(Clone, PartialEq, Message)]
+pub struct UserDictionary {
+    #[prost(uint64, optional, tag = "1")]
+    pub id: Option<u64>,
+    #[prost(bool, optional, tag = "2")]
+    pub enabled: Option<bool>,
+    #[prost(string, optional, tag = "3")]
+    pub name: Option<String>,
+    #[prost(bool, optional, tag = "4")]
+    pub removed: Option<bool>,
+    #[prost(bool, optional, tag = "5")]
+    pub syncable: Option<bool>,
+    #[prost(message, repeated, tag = "6")]
+    pub entries: Vec<UserDictionaryEntry>,
+}
+
+/// UserDictionaryStorage
+#[derive(Clone, PartialEq, Message)]
+pub struct UserDictionaryStorage {
+    #[prost(message, repeated, tag = "2")]
+    pub dictionaries: Vec<UserDictionary>,
+}
+
+/// ユーザー辞書の品詞名を、UserDictionary.PosTypeの値にする
+/// PosTypeは、MOZC_USER_POSと同じ順に1から番号が付けられている。
+pub fn pos_type(user_pos: &str) -> Option<i32> {
+    MOZC_USER_POS.iter().position(|&p| p == user_pos).map(|i| i as i32 + 1)
+}
+
+/// UserDictionary.PosTypeの値を、ユーザー辞書の品詞名にする
+pub fn pos_name(pos_type: i32) -> Option<&'static str> {
+    usize::try_from(pos_type - 1).ok().and_then(|i| MOZC_USER_POS.get(i)).copied()
+}
+
+/// 辞書名から辞書のIDを決める
+/// Mozcは辞書を作るたびに乱数でIDを付けるが、同じ入力からは同じファイルが生成されるよう、名前のFNV-1aハッシュを用いる。
+pub fn dictionary_id(name: &str) -> u64 {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    hash.max(1)
+}
+
+/// 入力元ごとの辞書を作る
+/// namesは入力元の優先順位と辞書名の対応。入力元ごとにmax_entries件を超える場合は、`名前 2`のように辞書を分ける。
+/// ユーザー辞書の品詞名がMozcの品詞(PosType)にないエントリーは含めず、それらのエントリーを返す。
+pub fn build_storage<'a>(dict_data: &'a DictionaryData, names: &HashMap<usize, String>, max_entries: usize, order: SortOrder) -> Result<(UserDictionaryStorage, Vec<&'a DictionaryEntry>), String> {
+    if max_entries == 0 {
+        return Err("--max-entries must be at least 1".to_string());
+    }
+    let mut by_source: BTreeMap<usize, Vec<&DictionaryEntry>> = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for entry in dict_data.user_output(order) {
+        if pos_type(&entry.pos).is_none() {
+            skipped.push(entry);
+            continue;
+        }
+        by_source.entry(entry.priority).or_default().push(entry);
+    }
+
+    let mut storage = UserDictionaryStorage::default();
+    for (priority, list) in &by_source {
+        let base = names.get(priority).cloned().unwrap_or_else(|| format!("mozcdict-ext {}", priority));
+        let chunks = split_by_yomi(list, max_entries)?;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let name = if chunks.len() > 1 { format!("{} {}", base, i + 1) } else { base.clone() };
+            storage.dictionaries.push(UserDictionary {
+                id: Some(dictionary_id(&name)),
+                enabled: Some(true),
+                name: Some(name),
+                removed: None,
+                syncable: None,
+                entries: chunk.iter().map(|entry| UserDictionaryEntry {
+                    key: Some(entry.key.yomi.clone()),
+                    value: Some(entry.key.surface.clone()),
+                    comment: None,
+                    pos: pos_type(&entry.pos),
+                }).collect(),
+            });
+        }
+    }
+    Ok((storage, skipped))
+}
+
+/// user_dictionary.dbを書き出す
+/// 辞書の数や、辞書ごとのエントリー数がMozcの上限を超える場合は、書き出さずにエラーとする。
+pub fn write_storage(path: &Path, storage: &UserDictionaryStorage) -> Result<(), Error> {
+    check_limits(storage, 0).map_err(|message| Error::Config { path: path.to_path_buf(), message })?;
+    let file = File::create(path).map_err(|e| Error::io(path, e))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&storage.encode_to_vec())
+        .and_then(|_| writer.flush())
+        .map_err(|e| Error::io(path, e))
+}
+
+/// user_dictionary.dbを読み込む
+pub fn read_storage(path: &Path) -> Result<UserDictionaryStorage, Error> {
+    let bytes = std::fs::read(path).map_err(|e| Error::io(path, e))?;
+    UserDictionaryStorage::decode(bytes.as_slice())
+        .map_err(|e| Error::Config { path: path.to_path_buf(), message: e.to_string() })
+}
+
+/// 書き出したuser_dictionary.dbを読み直し、生成した辞書がそのまま含まれているか確かめる
+pub fn verify_storage(path: &Path, generated: &UserDictionaryStorage) -> Result<(), Error> {
+    let storage = read_storage(path)?;
+    for dict in &generated.dictionaries {
+        if !storage.dictionaries.iter().any(|written| written == dict) {
+            return Err(Error::Config { path: path.to_path_buf(), message: format!("{} was not written correctly", dict.name()) });
+        }
+    }
+    Ok(())
+}
+
+/// Mozcのユーザー辞書の数の上限
+pub const MAX_USER_DICTIONARIES: usize = 100;
+
+// 生成した辞書と、残す既存の辞書(kept個)を合わせて、Mozcの上限に収まるか確かめる
+fn check_limits(generated: &UserDictionaryStorage, kept: usize) -> Result<(), String> {
+    for dict in &generated.dictionaries {
+        if dict.entries.len() > MAX_USER_DICT_ENTRIES {
+            return Err(format!("{} has {} entries; Mozc allows at most {} per dictionary",
+                dict.name(), dict.entries.len(), MAX_USER_DICT_ENTRIES));
+        }
+    }
+    let added = generated.dictionaries.len();
+    if kept + added > MAX_USER_DICTIONARIES {
+        return Err(format!("{} existing and {} generated dictionaries exceed the Mozc limit of {}",
+            kept, added, MAX_USER_DICTIONARIES));
+    }
+    Ok(())
+}
+
+/// dict-to-mozcが生成した辞書の名前に付ける接頭辞
+/// --installでは、この接頭辞で始まる辞書をすべて置き換える。
+pub const GENERATED_PREFIX: &str = "mozcdict-ext ";
+
+/// Mozcのユーザー辞書ファイルの既定のパス
+/// `$XDG_CONFIG_HOME/mozc/user_dictionary.db`。それがなく、古い`~/.mozc`にあれば、そちらを用いる。
+pub fn default_path() -> Option<PathBuf> {
+    let path = config_home()?.join("mozc").join(USER_DB_FILE);
+    let legacy = PathBuf::from(std::env::var_os("HOME")?).join(".mozc").join(USER_DB_FILE);
+    if !path.exists() && legacy.exists() {
+        Some(legacy)
+    } else {
+        Some(path)
+    }
+}
+
+/// --installの結果
+#[derive(Clone, Debug, Default)]
+pub struct InstallSummary {
+    /// そのまま残した辞書の数
+    pub kept: usize,
+    /// 置き換えた、生成済みの辞書の数
+    pub replaced: usize,
+    /// 書き込んだ辞書の数
+    pub added: usize,
+    /// 今回作成した、最初に書き込む前のファイルのバックアップ
+    pub backup: Option<PathBuf>,
+    /// 今回書き込む直前のファイルのバックアップ
+    pub previous: Option<PathBuf>,
+}
+
+// user_dictionary.dbの最上位のフィールド
+// 利用者の辞書は、このプログラムが知らないフィールドも含めてそのまま書き戻すため、デコードせずに扱う。
+struct RawField<'a> {
+    tag: u32,
+    // キーを含むフィールド全体
+    bytes: &'a [u8],
+    // 長さ付きのフィールドの中身
+    payload: Option<&'a [u8]>,
+}
+
+fn split_fields(bytes: &[u8]) -> Result<Vec<RawField<'_>>, DecodeError> {
+    let mut fields = Vec::new();
+    let mut buf = bytes;
+    while !buf.is_empty() {
+        let start = bytes.len() - buf.len();
+        let (tag, wire_type) = decode_key(&mut buf)?;
+        let mut payload = None;
+        match wire_type {
+            WireType::Varint => { decode_varint(&mut buf)?; },
+            WireType::SixtyFourBit | WireType::ThirtyTwoBit => {
+                let size = if wire_type == WireType::SixtyFourBit { 8 } else { 4 };
+                if buf.len() < size {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                buf = &buf[size..];
+            },
+            WireType::LengthDelimited => {
+                let len = decode_varint(&mut buf)? as usize;
+                if buf.len() < len {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                payload = Some(&buf[..len]);
+                buf = &buf[len..];
+            },
+            _ => return Err(DecodeError::new("unexpected group")),
+        }
+        let end = bytes.len() - buf.len();
+        fields.push(RawField { tag, bytes: &bytes[start..end], payload });
+    }
+    Ok(fields)
+}
+
+/// 既存のuser_dictionary.dbに、生成した辞書を書き込む
+/// GENERATED_PREFIXで始まる辞書は、前回までに生成したものとしてすべて置き換え、利用者の辞書はそのまま残す。
+/// 元のファイルは`user_dictionary.db.bak`に残す。`.bak`が既にあれば上書きしないので、最初に書き込む前のファイルが残る。
+/// 書き込む直前のファイルは、毎回`user_dictionary.db.prev`に残す。
+/// 辞書の数や、辞書ごとのエントリー数がMozcの上限を超える場合は、書き込まずにエラーとする。
+pub fn install_storage(path: &Path, generated: &UserDictionaryStorage) -> Result<InstallSummary, Error> {
+    let config_error = |message: String| Error::Config { path: path.to_path_buf(), message };
+    let existing = if path.exists() {
+        std::fs::read(path).map_err(|e| Error::io(path, e))?
+    } else {
+        Vec::new()
+    };
+    let fields = split_fields(&existing).map_err(|e| config_error(e.to_string()))?;
+
+    let mut summary = InstallSummary::default();
+    let mut merged = Vec::with_capacity(existing.len());
+    for field in &fields {
+        if let (DICTIONARIES_TAG, Some(payload)) = (field.tag, field.payload) {
+            let dict = UserDictionary::decode(payload).map_err(|e| config_error(e.to_string()))?;
+            if dict.name().starts_with(GENERATED_PREFIX) {
+                summary.replaced += 1;
+                continue;
+            }
+            summary.kept += 1;
+        }
+        merged.extend_from_slice(field.bytes);
+    }
+    check_limits(generated, summary.kept).map_err(config_error)?;
+    for dict in &generated.dictionaries {
+        prost::encoding::message::encode(DICTIONARIES_TAG, dict, &mut merged);
+        summary.added += 1;
+    }
+
+    // 元のファイルを残してから、一時ファイルに書いて置き換える
+    if path.exists() {
+        let backup = append_extension(path, "bak");
+        if !backup.exists() {
+            std::fs::copy(path, &backup).map_err(|e| Error::io(&backup, e))?;
+            summary.backup = Some(backup);
+        }
+        let previous = append_extension(path, "prev");
+        std::fs::copy(path, &previous).map_err(|e| Error::io(&previous, e))?;
+        summary.previous = Some(previous);
+    } else if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+    }
+    let temp = append_extension(path, "tmp");
+    std::fs::write(&temp, &merged).map_err(|e| Error::io(&temp, e))?;
+    std::fs::rename(&temp, path).map_err(|e| Error::io(path, e))?;
+    Ok(summary)
+}
+
+// user_dictionary.db.bakのように、ファイル名に拡張子を加える
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionary::DictionaryKey;
+    use crate::test_util::temp_path;
+
+    fn entry(yomi: &str, surface: &str, pos: &str, priority: usize) -> DictionaryEntry {
+        DictionaryEntry {
+            key: DictionaryKey { yomi: yomi.to_string(), surface: surface.to_string(), hinshi_id: 0 },
+            cost: 6000,
+            raw_cost: 6000,
+            pos: pos.to_string(),
+            priority,
+            line: 1,
+        }
+    }
+
+    #[test]
+    fn storage_round_trips() {
+        let mut dict_data = DictionaryData::new();
+        dict_data.add(entry("かく", "書く", "動詞カ行五段", 0), true);
+        dict_data.add(entry("とうきょう", "東京", "地名", 1), true);
+        dict_data.add(entry("ふぃらー", "フィラー", "フィラー", 1), true);
+        let names = HashMap::from([(0, "mozcdict-ext a".to_string()), (1, "mozcdict-ext b".to_string())]);
+        let (storage, skipped) = build_storage(&dict_data, &names, 10, SortOrder::Yomi).unwrap();
+        assert_eq!(skipped.iter().map(|e| e.key.surface.as_str()).collect::<Vec<_>>(), ["フィラー"]);
+
+        let path = temp_path("round_trip.db");
+        write_storage(&path, &storage).unwrap();
+        verify_storage(&path, &storage).unwrap();
+        let decoded = read_storage(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(decoded, storage);
+
+        let entries: Vec<(&str, &str, &str, Option<&str>, &str)> = decoded.dictionaries.iter()
+            .flat_map(|dict| dict.entries.iter().map(move |e| {
+                (dict.name(), e.key(), e.value(), pos_name(e.pos()), e.comment())
+            }))
+            .collect();
+        assert_eq!(entries, [
+            ("mozcdict-ext a", "かく", "書く", Some("動詞カ行五段"), ""),
+            ("mozcdict-ext b", "とうきょう", "東京", Some("地名"), ""),
+        ]);
+    }
+
+    #[test]
+    fn pos_type_follows_mozc_order() {
+        assert_eq!(pos_type("名詞"), Some(1));
+        assert_eq!(pos_type("抑制単語"), Some(44));
+        assert_eq!(pos_type("フィラー"), None);
+        for name in MOZC_USER_POS {
+            assert_eq!(pos_name(pos_type(name).unwrap()), Some(name));
+        }
+    }
+
+    #[test]
+    fn write_rejects_storage_over_the_mozc_limits() {
+        let path = temp_path("limits.db");
+        let dict = |name: String, entries: usize| UserDictionary {
+            name: Some(name),
+            entries: vec![UserDictionaryEntry::default(); entries],
+            ..Default::default()
+        };
+        let too_many = UserDictionaryStorage {
+            dictionaries: (0..=MAX_USER_DICTIONARIES).map(|i| dict(format!("mozcdict-ext {}", i), 1)).collect(),
+        };
+        let too_large = UserDictionaryStorage {
+            dictionaries: vec![dict("mozcdict-ext a".to_string(), MAX_USER_DICT_ENTRIES + 1)],
+        };
+        for storage in [too_many, too_large] {
+            assert!(matches!(write_storage(&path, &storage), Err(Error::Config { .. })));
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn install_replaces_all_generated_dictionaries_and_keeps_backups() {
+        let path = temp_path("install.db");
+        let backup = append_extension(&path, "bak");
+        let previous = append_extension(&path, "prev");
+        let storage = |names: &[&str]| UserDictionaryStorage {
+            dictionaries: names.iter().map(|name| UserDictionary { name: Some(name.to_string()), ..Default::default() }).collect(),
+        };
+        let user = storage(&["my words"]);
+        write_storage(&path, &user).unwrap();
+
+        let first = install_storage(&path, &storage(&["mozcdict-ext a", "mozcdict-ext old"])).unwrap();
+        assert_eq!(first.backup.as_ref(), Some(&backup));
+        assert_eq!(first.previous.as_ref(), Some(&previous));
+        assert_eq!((first.kept, first.replaced, first.added), (1, 0, 2));
+
+        // 今回生成しなかった`mozcdict-ext old`も置き換えられる
+        let generated = storage(&["mozcdict-ext a"]);
+        let second = install_storage(&path, &generated).unwrap();
+        assert_eq!(second.backup, None);
+        assert_eq!((second.kept, second.replaced, second.added), (1, 2, 1));
+        verify_storage(&path, &generated).unwrap();
+        assert_eq!(read_storage(&path).unwrap(), storage(&["my words", "mozcdict-ext a"]));
+        assert_eq!(read_storage(&backup).unwrap(), user);
+        assert_eq!(read_storage(&previous).unwrap(), storage(&["my words", "mozcdict-ext a", "mozcdict-ext old"]));
+
+        for file in [&path, &backup, &previous] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! 読みの変換やコスト計算などの補助関数
+
+use std::path::PathBuf;
+
+use lazy_regex::regex;
+use regex::Captures;
+
+use kanaria::string::{UCSStr, ConvertType};
+use kanaria::utils::ConvertTarget;
+
+/// ByHand辞書のコスト(暫定で一律)
+pub const BYHAND_COST: i32 = 6500;
+/// Mozcのユーザー辞書型式から読み込んだエントリーのコスト(コストの列がないため一律)
+pub const MOZC_USER_COST: i32 = 6500;
+
+/// `$XDG_CONFIG_HOME`(XDG_CONFIG_HOMEがなければ`~/.config`)
+pub fn config_home() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")),
+    }
+}
+
+/// mozcdict-extの設定ファイルを置くディレクトリ
+/// `$XDG_CONFIG_HOME/mozcdict-ext`(XDG_CONFIG_HOMEがなければ`~/.config/mozcdict-ext`)
+pub fn config_dir() -> Option<PathBuf> {
+    Some(config_home()?.join("mozcdict-ext"))
+}
+
+/// カタカナから読みを平仮名へ
+pub fn convert_to_hiragana(text: &str) -> String {
+    let target: Vec<char> = text.chars().collect();
+    let mut yomi: String = UCSStr::convert(&target, ConvertType::Hiragana, ConvertTarget::ALL).iter().collect();
+    yomi = yomi.replace("ゐ", "い").replace("ゑ", "え");
+    yomi
+}
+
+/// Unicode Escapeの記述が含まれる場合、それを変換する。
+/// 文字として不正なコードポイント(サロゲートなど)が含まれる場合はエラーとする。
+pub fn unicode_escape_to_char(text: &str) -> Result<String, String> {
+    let mut invalid = None;
+    let result = regex!(r#"\\u([0-9a-fA-F]{4})"#).replace_all(text, |caps: &Captures| {
+        let num: u32 = u32::from_str_radix(&caps[1], 16).unwrap();
+        match std::char::from_u32(num) {
+            Some(c) => c.to_string(),
+            None => {
+                invalid.get_or_insert_with(|| caps[0].to_string());
+                caps[0].to_string()
+            }
+        }
+    });
+    match invalid {
+        Some(escape) => Err(format!("invalid unicode escape {}", escape)),
+        None => Ok(result.to_string()),
+    }
+}
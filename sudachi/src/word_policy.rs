@@ -0,0 +1,131 @@
+//! 英語、全角英語、固有名詞の扱い(lib/dictutils.rbのcheck_english、check_fullwidth_english、check_proper)
+
+use lazy_regex::{Lazy, Regex, lazy_regex};
+
+use crate::reject::RejectReason;
+
+// ASCII、記号、CJKの記号及び句読点、句読点、空白のみからなる表記
+// Onigmoの\p{In_CJK_Symbols_and_Punctuation}は、U+3000からU+303Fのブロック
+static ENGLISH: Lazy<Regex> = lazy_regex!(r"^[\x00-\x7F\p{Symbol}\u{3000}-\u{303F}\p{Punctuation}\p{White_Space}]+$");
+
+// 記号、CJKの記号及び句読点、句読点、空白、半角・全角形のみからなる表記
+// Onigmoの\p{In_Halfwidth_and_Fullwidth_Forms}は、U+FF00からU+FFEFのブロック
+static FULLWIDTH_ENGLISH: Lazy<Regex> = lazy_regex!(r"^[\p{Symbol}\u{3000}-\u{303F}\p{Punctuation}\p{White_Space}\u{FF00}-\u{FFEF}]+$");
+
+// -Sの指定時に、英語とみなすために必要な文字(英字)
+// 記号と数字のみからなる表記は、記号として扱う。
+static ASCII_LETTER: Lazy<Regex> = lazy_regex!(r"[A-Za-z]");
+
+// -Sの指定時に、全角英語とみなすために必要な文字(全角英字と半角カナ)
+static FULLWIDTH_LETTER: Lazy<Regex> = lazy_regex!(r"[Ａ-Ｚａ-ｚ\u{FF66}-\u{FF9F}]");
+
+/// 英語、全角英語、固有名詞を辞書に含めるかどうか
+/// 既定では、固有名詞ではない英語への変換のみを除外する。全角英語と、固有名詞の英語は含める。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WordPolicy {
+    /// 英語への変換を含める(--english)
+    pub english: bool,
+    /// 英語への変換は、固有名詞の場合のみ含める(--english-proper)
+    pub english_proper: bool,
+    /// 全角英語と半角カナへの変換を含める(--fullwidth-english)
+    pub fullwidth_english: bool,
+    /// 全角英語と半角カナへの変換は、固有名詞の場合のみ含める(--fullwidth-english-proper)
+    pub fullwidth_english_proper: bool,
+    /// 固有名詞を除外する(--no-proper)
+    pub no_proper: bool,
+}
+
+impl Default for WordPolicy {
+    fn default() -> Self {
+        Self {
+            english: false,
+            english_proper: true,
+            fullwidth_english: true,
+            fullwidth_english_proper: true,
+            no_proper: false,
+        }
+    }
+}
+
+impl WordPolicy {
+    /// 表記と品詞の文字列から、除外する場合はその理由を返す
+    /// lib/dictutils.rbと同じく、固有名詞、英語、全角英語の順に判定する。
+    /// symbolsは記号を含める指定(-S)で、その場合は記号と数字のみからなる表記を英語とみなさない。
+    pub fn check(&self, surface: &str, clsexpr: &str, symbols: bool) -> Option<RejectReason> {
+        let proper = clsexpr.contains("固有名詞");
+        let english = ENGLISH.is_match(surface) && (!symbols || ASCII_LETTER.is_match(surface));
+        let fullwidth = FULLWIDTH_ENGLISH.is_match(surface) && (!symbols || FULLWIDTH_LETTER.is_match(surface));
+        if self.no_proper && proper {
+            Some(RejectReason::Proper)
+        } else if !self.english && english && !(self.english_proper && proper) {
+            Some(RejectReason::Alphabet)
+        } else if !self.fullwidth_english && fullwidth && !(self.fullwidth_english_proper && proper) {
+            Some(RejectReason::Fullwidth)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOUN: &str = "名詞,普通名詞,一般,*,*,*";
+    const PROPER: &str = "名詞,固有名詞,一般,*,*,*";
+
+    #[test]
+    fn default_drops_only_common_english() {
+        let policy = WordPolicy::default();
+        assert_eq!(policy.check("apple", NOUN, false), Some(RejectReason::Alphabet));
+        assert_eq!(policy.check("Apple", PROPER, false), None);
+        assert_eq!(policy.check("ＡＢＣ", NOUN, false), None);
+        assert_eq!(policy.check("東京", PROPER, false), None);
+        assert_eq!(policy.check("漢字", NOUN, false), None);
+    }
+
+    #[test]
+    fn symbols_keep_symbol_and_digit_surfaces() {
+        let policy = WordPolicy::default();
+        assert_eq!(policy.check("123", NOUN, false), Some(RejectReason::Alphabet));
+        assert_eq!(policy.check("!?", NOUN, false), Some(RejectReason::Alphabet));
+        assert_eq!(policy.check("123", NOUN, true), None);
+        assert_eq!(policy.check("!?", NOUN, true), None);
+        assert_eq!(policy.check("A-1", NOUN, true), Some(RejectReason::Alphabet));
+    }
+
+    #[test]
+    fn english_keeps_all_english() {
+        let policy = WordPolicy { english: true, ..WordPolicy::default() };
+        assert_eq!(policy.check("apple", NOUN, false), None);
+        assert_eq!(policy.check("Apple", PROPER, false), None);
+    }
+
+    #[test]
+    fn no_english_proper_drops_proper_english() {
+        let policy = WordPolicy { english_proper: false, ..WordPolicy::default() };
+        assert_eq!(policy.check("Apple", PROPER, false), Some(RejectReason::Alphabet));
+        assert_eq!(policy.check("東京", PROPER, false), None);
+    }
+
+    #[test]
+    fn no_fullwidth_english_keeps_proper_unless_asked() {
+        let policy = WordPolicy { fullwidth_english: false, ..WordPolicy::default() };
+        assert_eq!(policy.check("ＡＢＣ", NOUN, false), Some(RejectReason::Fullwidth));
+        assert_eq!(policy.check("ｶﾀｶﾅ", NOUN, false), Some(RejectReason::Fullwidth));
+        assert_eq!(policy.check("ＡＢＣ", PROPER, false), None);
+        assert_eq!(policy.check("１２３", NOUN, false), Some(RejectReason::Fullwidth));
+        assert_eq!(policy.check("１２３", NOUN, true), None);
+
+        let policy = WordPolicy { fullwidth_english_proper: false, ..policy };
+        assert_eq!(policy.check("ＡＢＣ", PROPER, false), Some(RejectReason::Fullwidth));
+    }
+
+    #[test]
+    fn no_proper_wins_over_english() {
+        let policy = WordPolicy { no_proper: true, english: true, ..WordPolicy::default() };
+        assert_eq!(policy.check("東京", PROPER, false), Some(RejectReason::Proper));
+        assert_eq!(policy.check("Apple", PROPER, false), Some(RejectReason::Proper));
+        assert_eq!(policy.check("apple", NOUN, false), None);
+    }
+}
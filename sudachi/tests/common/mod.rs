@@ -0,0 +1,20 @@
+//! テストで共通に用いる補助関数
+//! 結合テストからは`mod common;`、ライブラリの単体テストからは`crate::test_util`として用いる。
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// テスト用の一時ファイルのパス
+/// 並列に実行されるテスト同士で重ならないよう、プロセスIDと呼び出しごとの通し番号を含める。
+pub fn temp_path(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("dict-to-mozc-{}-{}-{}", std::process::id(), n, name))
+}
+
+/// textを書き込んだ一時ファイルのパス
+pub fn temp_file(name: &str, text: &str) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, text).unwrap();
+    path
+}
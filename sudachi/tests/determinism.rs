@@ -0,0 +1,37 @@
+//! 同じ入力からは、常に同じ辞書が出力されること
+
+use std::path::{Path, PathBuf};
+
+use dict_to_mozc::{DictFormat, DictionaryData, InputSpec, PosResolver, ReadOptions, Rejects, SortOrder, read_dictionary};
+
+fn data(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name)
+}
+
+// id.defの読み込みから出力までを、毎回新しい状態で行う
+fn convert(user_dict_flag: bool, order: SortOrder) -> String {
+    let mut resolver = PosResolver::load(&data("id.def")).unwrap();
+    let input = InputSpec { format: DictFormat::Sudachi, path: data("sudachi.csv") };
+    let opts = ReadOptions { user_dict_flag, chimei_flag: true, strict: true, ..Default::default() };
+    let mut dict_data = DictionaryData::new();
+    read_dictionary(&input, &mut resolver, &mut dict_data, &opts, &mut Rejects::new()).unwrap();
+    let mut out = Vec::new();
+    dict_data.output(&mut out, user_dict_flag, order).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn system_dictionary_is_identical_across_runs() {
+    for order in [SortOrder::Yomi, SortOrder::Id, SortOrder::Cost, SortOrder::Surface, SortOrder::Mozc] {
+        let first = convert(false, order);
+        assert!(!first.is_empty());
+        assert_eq!(first, convert(false, order), "sort {}", order);
+    }
+}
+
+#[test]
+fn user_dictionary_is_identical_across_runs() {
+    let first = convert(true, SortOrder::Yomi);
+    assert!(!first.is_empty());
+    assert_eq!(first, convert(true, SortOrder::Yomi));
+}
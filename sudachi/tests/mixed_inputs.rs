@@ -0,0 +1,52 @@
+//! 複数の入力元を読み込んでも、先に読み込んだ入力元によって後の入力元の判定が変わらないこと
+
+mod common;
+
+use dict_to_mozc::{DictFormat, DictionaryData, IdDef, InputSpec, PosResolver, ReadOptions, RejectReason, Rejects, read_dictionary};
+
+use common::temp_file;
+
+// 固有名詞の品詞を持たないid.def
+// Sudachiの地名の品詞はどの行とも対応せず、普通名詞(ID 1)として追加される。
+fn resolver() -> PosResolver {
+    let id_def: IdDef = [
+        ("名詞,普通名詞,*,*,*,*,*".to_string(), 1),
+        ("動詞,一般,*,*,*,*,*".to_string(), 2),
+    ].into_iter().collect();
+    PosResolver::new(id_def, 1)
+}
+
+fn options(chimei_flag: bool, priority: usize) -> ReadOptions<'static> {
+    ReadOptions { chimei_flag, priority, strict: true, ..Default::default() }
+}
+
+// utdictを読み込み、utdictで除外された件数を返す
+fn read_utdict(resolver: &mut PosResolver, dict_data: &mut DictionaryData) -> Rejects {
+    let path = temp_file("utdict.txt", "かんじ\t1\t1\t3000\t漢字\nけん\t1\t1\t3000\t県\n");
+    let input = InputSpec { format: DictFormat::UtDict, path: path.clone() };
+    let mut rejects = Rejects::new();
+    read_dictionary(&input, resolver, dict_data, &options(false, 1), &mut rejects).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    rejects
+}
+
+#[test]
+fn sudachi_before_utdict_does_not_change_utdict_rejects() {
+    let mut alone = DictionaryData::new();
+    let expected = read_utdict(&mut resolver(), &mut alone);
+
+    let mut resolver = resolver();
+    let mut mixed = DictionaryData::new();
+    let path = temp_file("sudachi.csv", "札幌,1,1,3000,札幌,名詞,固有名詞,地名,一般,*,*,サッポロ,札幌,*,A,*,*,*,*\n");
+    let input = InputSpec { format: DictFormat::Sudachi, path: path.clone() };
+    read_dictionary(&input, &mut resolver, &mut mixed, &options(true, 0), &mut Rejects::new()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(resolver.id_def.keys().any(|key| key.contains("地名")));
+    assert_eq!(resolver.id_def_pos(1), "名詞,普通名詞,*,*,*,*,*");
+
+    let actual = read_utdict(&mut resolver, &mut mixed);
+    for reason in RejectReason::ALL {
+        assert_eq!(actual.count(reason), expected.count(reason), "{}", reason.code());
+    }
+    assert_eq!(actual.total(), 0);
+}
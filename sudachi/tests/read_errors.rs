@@ -0,0 +1,58 @@
+//! 不正な行は、ファイル名と行番号を持つ型付きのエラーになること
+
+mod common;
+
+use std::path::PathBuf;
+
+use dict_to_mozc::{DictFormat, DictionaryData, Error, IdDef, InputSpec, PosResolver, ReadOptions, ReadStats, RejectReason, Rejects, read_dictionary};
+
+use common::temp_file;
+
+const GOOD: &str = "漢字,5146,5146,3000,漢字,名詞,普通名詞,一般,*,*,*,カンジ,漢字,*,A,*,*,*,*\n";
+
+// 2行目にbadを置いたSudachiの辞書を読み込む
+fn read(name: &str, bad: &str, strict: bool) -> (PathBuf, Result<ReadStats, Error>, Rejects) {
+    let path = temp_file(&format!("{}.csv", name), &format!("{}{}{}", GOOD, bad, GOOD.replace("漢字", "感じ")));
+    let opts = ReadOptions { chimei_flag: true, strict, ..Default::default() };
+    let input = InputSpec { format: DictFormat::Sudachi, path: path.clone() };
+    let mut rejects = Rejects::new();
+    let result = read_dictionary(&input, &mut PosResolver::new(IdDef::new(), 0), &mut DictionaryData::new(), &opts, &mut rejects);
+    std::fs::remove_file(&path).unwrap();
+    (path, result, rejects)
+}
+
+#[test]
+fn short_line_is_a_columns_error() {
+    let (path, result, _) = read("columns", "漢字,5146,5146\r\n", true);
+    match result {
+        Err(err @ Error::Columns { .. }) => {
+            assert_eq!(err.path(), path);
+            assert_eq!(err.line(), Some(2));
+            assert_eq!(err.to_string(), format!("{}:2: expected at least 12 columns, found 3", path.display()));
+        },
+        other => panic!("unexpected: {:?}", other),
+    }
+}
+
+#[test]
+fn bad_cost_is_a_field_error() {
+    let (path, result, _) = read("field", &GOOD.replace("3000", "x"), true);
+    match result {
+        Err(err @ Error::Field { .. }) => {
+            assert_eq!(err.path(), path);
+            assert_eq!(err.line(), Some(2));
+            assert_eq!(err.to_string(), format!("{}:2: field cost (column 4): not a number: \"x\"", path.display()));
+        },
+        other => panic!("unexpected: {:?}", other),
+    }
+}
+
+#[test]
+fn bad_lines_are_skipped_unless_strict() {
+    for bad in ["漢字,5146,5146\n".to_string(), GOOD.replace("3000", "x")] {
+        let (_, result, rejects) = read("skip", &bad, false);
+        let stats = result.unwrap();
+        assert_eq!((stats.records, stats.bad_lines), (3, 1));
+        assert_eq!(rejects.count(RejectReason::ParseError), 1);
+    }
+}